@@ -0,0 +1,176 @@
+// Animáció szerkesztés: képkockák törlése, duplikálása, mozgatása, késleltetése, megfordítása
+// és vágás egy tartományra. A képkockák a fájlban maradnak, csak a lejátszási sorrend (order)
+// és a késleltetések változnak; mentéskor a szerkesztett sorrend kerül a GIF / WebP fájlba.
+
+use std::time::Duration;
+
+use crate::ImageViewer;
+use crate::image_processing::AnimatedImage;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FrameEdit {
+    Delete,
+    Duplicate,
+    MoveBack,
+    MoveForward,
+    Delay(Duration),
+    Reverse,
+    Crop,
+}
+
+impl AnimatedImage {
+    /// A [first, last] tartomány (lejátszási sorszámok) szerkesztése; az új kijelölést adja vissza.
+    pub fn edit(&mut self, edit: FrameEdit, first: usize, last: usize) -> Result<(usize, usize), String> {
+        let total = self.order.len();
+        if total == 0 {
+            return Err("The animation has no frames".to_string());
+        }
+        let last = last.min(total - 1);
+        let first = first.min(last);
+        let selection = match edit {
+            FrameEdit::Delete => {
+                if last - first + 1 >= total {
+                    return Err("At least one frame must remain".to_string());
+                }
+                self.order.drain(first..=last);
+                self.delays.drain(first..=last);
+                let first = first.min(self.order.len() - 1);
+                (first, first)
+            }
+            FrameEdit::Duplicate => {
+                let order: Vec<usize> = self.order[first..=last].to_vec();
+                let delays: Vec<Duration> = self.delays[first..=last].to_vec();
+                self.order.splice(last + 1..last + 1, order);
+                self.delays.splice(last + 1..last + 1, delays);
+                (last + 1, 2 * last + 1 - first)
+            }
+            FrameEdit::MoveBack => {
+                if first == 0 {
+                    return Ok((first, last));
+                }
+                self.order[first - 1..=last].rotate_left(1);
+                self.delays[first - 1..=last].rotate_left(1);
+                (first - 1, last - 1)
+            }
+            FrameEdit::MoveForward => {
+                if last + 1 >= total {
+                    return Ok((first, last));
+                }
+                self.order[first..=last + 1].rotate_right(1);
+                self.delays[first..=last + 1].rotate_right(1);
+                (first + 1, last + 1)
+            }
+            FrameEdit::Delay(delay) => {
+                self.delays[first..=last].fill(delay);
+                (first, last)
+            }
+            FrameEdit::Reverse => {
+                self.order[first..=last].reverse();
+                self.delays[first..=last].reverse();
+                (first, last)
+            }
+            FrameEdit::Crop => {
+                self.order.truncate(last + 1);
+                self.delays.truncate(last + 1);
+                self.order.drain(..first);
+                self.delays.drain(..first);
+                (0, last - first)
+            }
+        };
+        self.total_frames = self.order.len();
+        Ok(selection)
+    }
+}
+
+impl ImageViewer {
+
+    /// Képkocka szerkesztés az idősávról; a lejátszás megáll, a kijelölés első kockája látszik.
+    pub fn edit_frames(&mut self, edit: FrameEdit, first: usize, last: usize) {
+        let Some(anim) = self.anim_data.as_mut() else {
+            return;
+        };
+        match anim.edit(edit, first, last) {
+            Ok((first, last)) => {
+                self.anim_playing = false;
+                self.anim_timer.stop();
+                self.anim_plays_done = 0;
+                self.anim_reverse = false;
+                self.refresh_timeline();
+                if let Some(t_ui) = &self.timeline_window {
+                    t_ui.set_sel_first(first as i32 + 1);
+                    t_ui.set_sel_last(last as i32 + 1);
+                }
+                self.show_frame(first);
+                self.sync_timeline();
+            }
+            Err(e) => self.notify(&e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::anim_stream::{FrameSource, FrameStream};
+    use crate::file_handlers::SaveFormat;
+
+    /// A szerkesztés csak a sorrendet és a késleltetéseket érinti, a fájlnak nem kell léteznie.
+    fn anim(count: usize) -> AnimatedImage {
+        let source = FrameSource { path: PathBuf::from("missing.gif"), format: SaveFormat::Gif, limits: image::Limits::default() };
+        let delays = (0..count).map(|i| Duration::from_millis(10 * (i as u64 + 1))).collect();
+        AnimatedImage::new(FrameStream::start(source, count, 0), delays, 0)
+    }
+
+    fn ms(anim: &AnimatedImage) -> Vec<u64> {
+        anim.delays.iter().map(|d| d.as_millis() as u64).collect()
+    }
+
+    #[test]
+    fn delete_removes_the_range_but_not_every_frame() {
+        let mut a = anim(4);
+        assert_eq!(a.edit(FrameEdit::Delete, 1, 2), Ok((1, 1)));
+        assert_eq!(a.order, vec![0, 3]);
+        assert_eq!(ms(&a), vec![10, 40]);
+        assert_eq!(a.total_frames, 2);
+        assert!(a.edit(FrameEdit::Delete, 0, 1).is_err());
+        assert_eq!(a.edit(FrameEdit::Delete, 1, 1), Ok((0, 0)));
+        assert_eq!(a.order, vec![0]);
+    }
+
+    #[test]
+    fn duplicate_inserts_the_copy_after_the_range() {
+        let mut a = anim(3);
+        assert_eq!(a.edit(FrameEdit::Duplicate, 1, 2), Ok((3, 4)));
+        assert_eq!(a.order, vec![0, 1, 2, 1, 2]);
+        assert_eq!(ms(&a), vec![10, 20, 30, 20, 30]);
+        assert_eq!(a.total_frames, 5);
+    }
+
+    #[test]
+    fn move_shifts_the_range_and_stops_at_the_ends() {
+        let mut a = anim(4);
+        assert_eq!(a.edit(FrameEdit::MoveBack, 1, 2), Ok((0, 1)));
+        assert_eq!(a.order, vec![1, 2, 0, 3]);
+        assert_eq!(a.edit(FrameEdit::MoveBack, 0, 1), Ok((0, 1)));
+        assert_eq!(a.order, vec![1, 2, 0, 3]);
+        assert_eq!(a.edit(FrameEdit::MoveForward, 0, 1), Ok((1, 2)));
+        assert_eq!(a.order, vec![0, 1, 2, 3]);
+        assert_eq!(a.edit(FrameEdit::MoveForward, 2, 3), Ok((2, 3)));
+        assert_eq!(ms(&a), vec![10, 20, 30, 40]);
+    }
+
+    #[test]
+    fn delay_reverse_and_crop() {
+        let mut a = anim(5);
+        assert_eq!(a.edit(FrameEdit::Delay(Duration::from_millis(70)), 3, 9), Ok((3, 4)));
+        assert_eq!(ms(&a), vec![10, 20, 30, 70, 70]);
+        assert_eq!(a.edit(FrameEdit::Reverse, 0, 2), Ok((0, 2)));
+        assert_eq!(a.order, vec![2, 1, 0, 3, 4]);
+        assert_eq!(ms(&a), vec![30, 20, 10, 70, 70]);
+        assert_eq!(a.edit(FrameEdit::Crop, 1, 3), Ok((0, 2)));
+        assert_eq!(a.order, vec![1, 0, 3]);
+        assert_eq!(ms(&a), vec![20, 10, 70]);
+        assert_eq!(a.total_frames, 3);
+    }
+}
@@ -0,0 +1,370 @@
+// Animációk mentése: minden képkocka az eredeti késleltetéssel, animált GIF-be vagy WebP-be.
+// A képkockák egyenként kerülnek a kódolóba, így a teljes animáció sosem kell egyszerre a memóriában;
+// a mentés háttérszálon fut, a haladás az értesítési sávban látszik.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Duration;
+use image::RgbaImage;
+use libwebp_sys::*;
+
+use crate::ImageViewer;
+use crate::colors::*;
+use crate::errors::*;
+use crate::atomic_save::AtomicFile;
+use crate::file_handlers::{SaveFormat, SaveSettings};
+use crate::image_processing::{AnimatedImage, rotate_and_color, worker_status};
+
+/// Képkockánként kódoló animáció: GIF-nél a kockák azonnal a fájlba íródnak, WebP-nél csak a
+/// tömörített adat gyűlik, és a fájl a `finish`-nél készül el.
+pub enum AnimWriter {
+    Gif(GifWriter),
+    Webp(WebpWriter),
+}
+
+impl AnimWriter {
+    /// Animált GIF; plays: a lejátszások száma, 0: végtelen ismétlés.
+    pub fn gif(path: &Path, plays: u32) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| e.to_string())?;
+        Ok(AnimWriter::Gif(GifWriter { file: Some(BufWriter::new(file)), encoder: None, plays }))
+    }
+
+    /// Animált WebP; a képkockák időbélyege a késleltetések összege (ms).
+    pub fn webp(path: &Path, plays: u32, quality: f32, lossless: bool) -> Result<Self, String> {
+        let mut config = WebPConfig::new().map_err(|_| "WebP encoder init failed".to_string())?;
+        config.lossless = if lossless { 1 } else { 0 };
+        config.alpha_compression = if lossless { 0 } else { 1 };
+        config.quality = quality;
+        Ok(AnimWriter::Webp(WebpWriter {
+            path: path.to_path_buf(),
+            config,
+            encoder: std::ptr::null_mut(),
+            size: (0, 0),
+            plays,
+            timestamp: 0,
+        }))
+    }
+
+    /// A következő képkocka; a méretet az első kocka adja, a többinek is ekkorának kell lennie.
+    pub fn add_frame(&mut self, frame: RgbaImage, delay: Duration) -> Result<(), String> {
+        match self {
+            AnimWriter::Gif(gif) => gif.add_frame(frame, delay),
+            AnimWriter::Webp(webp) => webp.add_frame(&frame, delay),
+        }
+    }
+
+    /// A fájl lezárása.
+    pub fn finish(self) -> Result<(), String> {
+        match self {
+            AnimWriter::Gif(gif) => gif.finish(),
+            AnimWriter::Webp(webp) => webp.finish(),
+        }
+    }
+}
+
+pub struct GifWriter {
+    file: Option<BufWriter<File>>, // az első képkockáig, utána a kódolóé
+    encoder: Option<gif::Encoder<BufWriter<File>>>,
+    plays: u32,
+}
+
+impl GifWriter {
+    fn add_frame(&mut self, mut frame: RgbaImage, delay: Duration) -> Result<(), String> {
+        let (Ok(width), Ok(height)) = (u16::try_from(frame.width()), u16::try_from(frame.height())) else {
+            return Err("The frame is too large for GIF".into());
+        };
+        if self.encoder.is_none() {
+            let file = self.file.take().ok_or("The GIF file is already closed")?;
+            let mut encoder = gif::Encoder::new(file, width, height, &[]).map_err(|e| e.to_string())?;
+            // a NETSCAPE kiterjesztés az első utáni ismétléseket számolja
+            let repeat = match self.plays {
+                0 => gif::Repeat::Infinite,
+                n => gif::Repeat::Finite((n - 1).min(u16::MAX as u32) as u16),
+            };
+            encoder.set_repeat(repeat).map_err(|e| e.to_string())?;
+            self.encoder = Some(encoder);
+        }
+        let Some(encoder) = self.encoder.as_mut() else {
+            return Err("The GIF file is already closed".into());
+        };
+        let mut gif_frame = gif::Frame::from_rgba_speed(width, height, &mut frame, 10);
+        // a GIF késleltetés 10 ms-os egységben
+        gif_frame.delay = (delay.as_millis() / 10).min(u16::MAX as u128) as u16;
+        gif_frame.dispose = gif::DisposalMethod::Background;
+        encoder.write_frame(&gif_frame).map_err(|e| e.to_string())
+    }
+
+    fn finish(self) -> Result<(), String> {
+        let Some(encoder) = self.encoder else {
+            return Err("The animation has no frames".into());
+        };
+        let file = encoder.into_inner().map_err(|e| e.to_string())?;
+        file.into_inner().map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+/// A libwebp animáció kódolója: a képkockát hozzáadáskor tömöríti, a nyers kocka nem marad meg.
+pub struct WebpWriter {
+    path: PathBuf,
+    config: WebPConfig,
+    encoder: *mut WebPAnimEncoder, // az első képkockánál jön létre, amikor a méret már ismert
+    size: (u32, u32),
+    plays: u32,
+    timestamp: i32,
+}
+
+impl WebpWriter {
+    fn add_frame(&mut self, frame: &RgbaImage, delay: Duration) -> Result<(), String> {
+        let (width, height) = frame.dimensions();
+        if self.encoder.is_null() {
+            self.encoder = unsafe {
+                let mut options = std::mem::MaybeUninit::<WebPAnimEncoderOptions>::uninit();
+                if WebPAnimEncoderOptionsInitInternal(options.as_mut_ptr(), WebPGetMuxABIVersion()) == 0 {
+                    return Err("WebP encoder init failed".into());
+                }
+                let mut options = options.assume_init();
+                // átlátszó háttér; 0: végtelen ismétlés
+                options.anim_params.bgcolor = 0;
+                options.anim_params.loop_count = self.plays.min(u16::MAX as u32) as i32;
+                WebPAnimEncoderNewInternal(width as i32, height as i32, &options, WebPGetMuxABIVersion())
+            };
+            if self.encoder.is_null() {
+                return Err("WebP encoder init failed".into());
+            }
+            self.size = (width, height);
+        } else if self.size != (width, height) {
+            return Err("The frames differ in size".into());
+        }
+        let mut picture = WebPPicture::new().map_err(|_| "WebP encoder init failed".to_string())?;
+        picture.use_argb = 1;
+        picture.width = width as i32;
+        picture.height = height as i32;
+        let added = unsafe {
+            let ok = WebPPictureImportRGBA(&mut picture, frame.as_raw().as_ptr(), width as i32 * 4) != 0
+                && WebPAnimEncoderAdd(self.encoder, &mut picture, self.timestamp, &self.config) != 0;
+            WebPPictureFree(&mut picture);
+            ok
+        };
+        if !added {
+            return Err(self.error());
+        }
+        self.timestamp = self.timestamp.saturating_add(delay.as_millis() as i32);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), String> {
+        if self.encoder.is_null() {
+            return Err("The animation has no frames".into());
+        }
+        let data = unsafe {
+            // a záró időbélyeg adja az utolsó kocka hosszát
+            let mut data = WebPData::default();
+            if WebPAnimEncoderAdd(self.encoder, std::ptr::null_mut(), self.timestamp, std::ptr::null()) == 0
+                || WebPAnimEncoderAssemble(self.encoder, &mut data) == 0 {
+                return Err(self.error());
+            }
+            let bytes = std::slice::from_raw_parts(data.bytes, data.size).to_vec();
+            WebPDataClear(&mut data);
+            bytes
+        };
+        std::fs::write(&self.path, data).map_err(|e| e.to_string())
+    }
+
+    fn error(&self) -> String {
+        let text = unsafe { WebPAnimEncoderGetError(self.encoder) };
+        if text.is_null() {
+            return "WebP encoding failed".into();
+        }
+        unsafe { std::ffi::CStr::from_ptr(text) }.to_string_lossy().into_owned()
+    }
+}
+
+impl Drop for WebpWriter {
+    fn drop(&mut self) {
+        if !self.encoder.is_null() {
+            unsafe { WebPAnimEncoderDelete(self.encoder) };
+        }
+    }
+}
+
+/// A futó animáció mentés: leállítás és az eredmény (a mentett fájl) a háttérszáltól.
+pub struct AnimSaveTask {
+    cancel: Arc<AtomicBool>,
+    receiver: Receiver<ViewerResult<PathBuf>>,
+}
+
+/// Egy animáció mentésének minden adata; a háttérszál ezzel dolgozik.
+struct AnimSaveJob {
+    anim: AnimatedImage,
+    modify: Option<(f32, ColorSettings)>, // "Save View": nagyítás és színkorrekció; None: az eredeti kockák
+    save_data: SaveSettings,
+    plays: u32,
+    keep_backup: bool,
+}
+
+impl ImageViewer {
+
+    /// Az animáció mentése háttérszálon, "Save View"-nál a forgatással, nagyítással és színkorrekcióval.
+    pub fn save_animation(&mut self, save_data: SaveSettings) {
+        let Some(anim) = self.anim_data.clone() else {
+            return;
+        };
+        if self.anim_save.is_some() {
+            self.notify("Animation save is already running");
+            return;
+        }
+        let Some(weak) = self.ui_handle.clone() else {
+            return;
+        };
+        let job = AnimSaveJob {
+            modify: if self.save_original { None } else { Some((self.magnify, self.color_settings)) },
+            save_data,
+            // bekapcsolt Loop: végtelen, különben a fájl ismétlésszáma (legalább egy lejátszás)
+            plays: if self.anim_loop { 0 } else { anim.plays.max(1) },
+            anim,
+            keep_backup: self.config.keep_backup,
+        };
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = channel();
+        self.anim_save = Some(AnimSaveTask { cancel: cancel.clone(), receiver });
+        std::thread::spawn(move || {
+            let status = |text: String, done: bool| worker_status(&weak, text, done);
+            let count = job.anim.order.len();
+            let result = write_animation(&job, &cancel, |i| {
+                status(format!("Saving frame {} / {} ... (Esc: cancel)", i + 1, count), false)
+            });
+            if result.is_ok() {
+                status(format!("{} frames saved to {}", count, job.save_data.full_path.display()), true);
+            }
+            let _ = sender.send(result.map(|()| job.save_data.full_path.clone()));
+            let weak = weak.clone();
+            let _ = slint::invoke_from_event_loop(move || {
+                if let Some(ui) = weak.upgrade() {
+                    ui.invoke_animation_saved();
+                }
+            });
+        });
+    }
+
+    /// A háttérszálon befejezett animáció mentés átvétele (az event loop-ból hívva).
+    pub fn animation_saved(&mut self) {
+        let Some(result) = self.anim_save.as_ref().and_then(|task| task.receiver.try_recv().ok()) else {
+            return;
+        };
+        self.anim_save = None;
+        match result {
+            Ok(path) => self.saved(&path),
+            Err(e) => self.notify_error(&e),
+        }
+    }
+
+    /// Esc: a futó animáció mentés leállítása; `false`, ha nem fut.
+    pub fn cancel_anim_save(&mut self) -> bool {
+        let Some(task) = &self.anim_save else {
+            return false;
+        };
+        task.cancel.store(true, Ordering::SeqCst);
+        true
+    }
+}
+
+/// A képkockák dekódolása, módosítása és kódolása az ideiglenes fájlba; a cél csak a végén cserélődik.
+fn write_animation(job: &AnimSaveJob, cancel: &AtomicBool, progress: impl Fn(usize)) -> ViewerResult<()> {
+    let target = &job.save_data.full_path;
+    let save_err = |e: &dyn std::fmt::Display| ViewerError::save(target, e);
+    let lut = job.modify.filter(|(_, colset)| colset.is_setted() || colset.is_blured()).map(|(_, colset)| {
+        let mut lut = Lut4ColorSettings::default();
+        lut.update_lut(&colset);
+        lut
+    });
+    let atomic = AtomicFile::new(target);
+    let mut writer = match job.save_data.saveformat {
+        SaveFormat::Webp => AnimWriter::webp(atomic.temp_path(), job.plays,
+            job.save_data.quality as f32, job.save_data.lossless),
+        _ => AnimWriter::gif(atomic.temp_path(), job.plays),
+    }.map_err(|e| save_err(&e))?;
+    job.anim.for_each_frame(|i, frame| {
+        if cancel.load(Ordering::SeqCst) {
+            return Err("cancelled".into());
+        }
+        progress(i);
+        let rgba = match &job.modify {
+            Some((magnify, colset)) => modified_frame(frame, *magnify, colset, lut.as_ref()),
+            None => frame.to_rgba8(),
+        };
+        writer.add_frame(rgba, job.anim.delays.get(i).copied().unwrap_or_default())
+    }).map_err(|e| save_err(&e))?;
+    writer.finish().map_err(|e| save_err(&e))?;
+    atomic.commit(job.keep_backup).map_err(|e| save_err(&e))
+}
+
+/// Nagyítás, forgatás és színkorrekció egy képkockán (a LUT-tal, a GPU nélkül, bármelyik szálon).
+fn modified_frame(frame: &image::DynamicImage, magnify: f32, colset: &ColorSettings, lut: Option<&Lut4ColorSettings>) -> RgbaImage {
+    if (magnify - 1.0).abs() > 0.001 {
+        let width = (frame.width() as f32 * magnify).round() as u32;
+        let height = (frame.height() as f32 * magnify).round() as u32;
+        let resized = frame.resize(width, height, image::imageops::FilterType::Lanczos3);
+        rotate_and_color(&resized, colset, lut)
+    } else {
+        rotate_and_color(frame, colset, lut)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anim_stream::{read_timing, FrameSource};
+
+    fn frames() -> Vec<(RgbaImage, Duration)> {
+        [(255, 40), (128, 70), (0, 120)].iter()
+            .map(|&(red, ms)| (RgbaImage::from_pixel(8, 6, image::Rgba([red, 0, 0, 255])), Duration::from_millis(ms)))
+            .collect()
+    }
+
+    fn write_and_time(mut writer: AnimWriter, path: &Path, format: SaveFormat) -> (Vec<u128>, u32) {
+        for (frame, delay) in frames() {
+            writer.add_frame(frame, delay).unwrap();
+        }
+        writer.finish().unwrap();
+        let timing = read_timing(path, format).unwrap();
+        let source = FrameSource { path: path.to_path_buf(), format, limits: image::Limits::default() };
+        let mut decoded = 0;
+        source.for_each_frame(|_, frame| {
+            assert_eq!((frame.width(), frame.height()), (8, 6));
+            decoded += 1;
+            Ok(())
+        }).unwrap();
+        assert_eq!(decoded, 3);
+        let _ = std::fs::remove_file(path);
+        (timing.delays.iter().map(|d| d.as_millis()).collect(), timing.plays)
+    }
+
+    #[test]
+    fn gif_writer_keeps_delays_and_plays() {
+        let path = std::env::temp_dir().join(format!("iviewer_anim_{}.gif", std::process::id()));
+        let writer = AnimWriter::gif(&path, 3).unwrap();
+        assert_eq!(write_and_time(writer, &path, SaveFormat::Gif), (vec![40, 70, 120], 3));
+    }
+
+    #[test]
+    fn webp_writer_keeps_delays_and_plays() {
+        let path = std::env::temp_dir().join(format!("iviewer_anim_{}.webp", std::process::id()));
+        let writer = AnimWriter::webp(&path, 0, 80.0, true).unwrap();
+        assert_eq!(write_and_time(writer, &path, SaveFormat::Webp), (vec![40, 70, 120], 0));
+    }
+
+    #[test]
+    fn writers_reject_empty_animation_and_size_change() {
+        let path = std::env::temp_dir().join(format!("iviewer_anim_empty_{}.webp", std::process::id()));
+        assert!(AnimWriter::webp(&path, 0, 80.0, false).unwrap().finish().is_err());
+        let mut writer = AnimWriter::webp(&path, 0, 80.0, false).unwrap();
+        writer.add_frame(RgbaImage::new(8, 6), Duration::from_millis(50)).unwrap();
+        assert!(writer.add_frame(RgbaImage::new(6, 8), Duration::from_millis(50)).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+}
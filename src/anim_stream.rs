@@ -0,0 +1,604 @@
+// Animációk folyamatos (streaming) dekódolása: a teljes animáció helyett csak egy csúszó
+// ablaknyi képkocka van a memóriában a lejátszott kocka körül.
+// A késleltetéseket egy első, könnyű menet olvassa ki a képadat dekódolása nélkül; a képkockákat
+// egy háttérszál dekódolja sorban (a GIF/APNG kockák az előzőre épülnek), visszaugráskor elölről.
+// Visszafelé lejátszásnál (pl. megfordított animáció) az ablak a kért kocka mögé kerül, és
+// csak akkor töltődik újra, amikor a következő néhány kocka elfogyott.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+use image::{AnimationDecoder, DynamicImage, ImageDecoder};
+
+use crate::ImageViewer;
+use crate::file_handlers::SaveFormat;
+use crate::errors::*;
+
+/// Ennyi képkocka legalább az ablakban marad; a kis animációk egészben a memóriában vannak.
+const MIN_WINDOW_FRAMES: usize = 8;
+
+/// Az animáció fájlja, amiből a háttérszál (újra) olvas.
+#[derive(Clone)]
+pub struct FrameSource {
+    pub path: PathBuf,
+    pub format: SaveFormat,
+    pub limits: image::Limits,
+}
+
+impl FrameSource {
+    /// Minden képkocka sorban, saját dekóderrel; bármelyik szálon hívható.
+    pub fn for_each_frame(&self, mut f: impl FnMut(usize, &DynamicImage) -> Result<(), String>) -> Result<(), String> {
+        let frames = open_frames(self)?;
+        for (i, frame) in frames.enumerate() {
+            let frame = frame.map_err(|e| e.to_string())?;
+            f(i, &DynamicImage::ImageRgba8(frame.into_buffer()))?;
+        }
+        Ok(())
+    }
+
+    /// A képkockák a megadott sorrendben (szerkesztett animáció), egyetlen előre haladó dekóderrel.
+    /// Csak azok a kockák maradnak meg, amelyekre a sorrendben később még szükség lesz, és az utolsó
+    /// felhasználás után azonnal eldobódnak; az eredeti sorrendnél semmi sem gyűlik.
+    pub fn for_each_in_order(&self, order: &[usize], mut f: impl FnMut(usize, &DynamicImage) -> Result<(), String>) -> Result<(), String> {
+        // minden forrás kocka utolsó helye a sorrendben
+        let mut last_use: HashMap<usize, usize> = HashMap::new();
+        for (i, src) in order.iter().enumerate() {
+            last_use.insert(*src, i);
+        }
+        let mut frames = open_frames(self)?;
+        let mut next = 0;
+        let mut held: HashMap<usize, DynamicImage> = HashMap::new();
+        for (i, src) in order.iter().enumerate() {
+            while !held.contains_key(src) {
+                let frame = match frames.next() {
+                    Some(frame) => frame.map_err(|e| e.to_string())?,
+                    None => return Err("The animation has fewer frames than its header".into()),
+                };
+                if last_use.get(&next).is_some_and(|last| *last >= i) {
+                    held.insert(next, DynamicImage::ImageRgba8(frame.into_buffer()));
+                }
+                next += 1;
+            }
+            if last_use.get(src) == Some(&i) {
+                if let Some(frame) = held.remove(src) {
+                    f(i, &frame)?;
+                }
+            } else if let Some(frame) = held.get(src) {
+                f(i, frame)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn open_frames(source: &FrameSource) -> Result<image::Frames<'static>, String> {
+    let reader = BufReader::new(File::open(&source.path).map_err(|e| e.to_string())?);
+    match source.format {
+        SaveFormat::Gif => {
+            let mut decoder = image::codecs::gif::GifDecoder::new(reader).map_err(|e| e.to_string())?;
+            decoder.set_limits(source.limits.clone()).map_err(|e| e.to_string())?;
+            Ok(decoder.into_frames())
+        }
+        SaveFormat::Webp => {
+            let mut decoder = image::codecs::webp::WebPDecoder::new(reader).map_err(|e| e.to_string())?;
+            decoder.set_limits(source.limits.clone()).map_err(|e| e.to_string())?;
+            Ok(decoder.into_frames())
+        }
+        SaveFormat::Png => {
+            // az fcTL késleltetést, a blend és dispose műveleteket az ApngDecoder alkalmazza
+            let mut decoder = image::codecs::png::PngDecoder::new(reader).map_err(|e| e.to_string())?;
+            decoder.set_limits(source.limits.clone()).map_err(|e| e.to_string())?;
+            Ok(decoder.apng().map_err(|e| e.to_string())?.into_frames())
+        }
+        _ => Err("This format has no animation".into()),
+    }
+}
+
+/// Az animáció időzítése a fájl szerint.
+pub struct AnimTiming {
+    pub delays: Vec<Duration>,
+    pub plays: u32, // a lejátszások száma, 0: végtelen
+}
+
+/// Első menet: a képkockák késleltetése és az ismétlésszám; None, ha a fájl nem animáció.
+pub fn read_timing(path: &Path, format: SaveFormat) -> Option<AnimTiming> {
+    let reader = BufReader::new(File::open(path).ok()?);
+    match format {
+        SaveFormat::Gif => gif_timing(reader),
+        SaveFormat::Webp => webp_timing(reader),
+        SaveFormat::Png => apng_timing(reader),
+        _ => None,
+    }
+}
+
+/// A tárolt késleltetés változatlanul; a 0 (meg nem adott) késleltetés a böngészőkhöz hasonlóan 100 ms.
+fn frame_delay(delay: Duration) -> Duration {
+    if delay.is_zero() { Duration::from_millis(100) } else { delay }
+}
+
+fn gif_timing(reader: BufReader<File>) -> Option<AnimTiming> {
+    // a képadat tömörítve marad, csak a képkocka fejlécek (és a késleltetés, 10 ms-ban) kellenek
+    let mut options = gif::DecodeOptions::new();
+    options.skip_frame_decoding(true);
+    let mut decoder = options.read_info(reader).ok()?;
+    let mut delays = Vec::new();
+    while let Some(frame) = decoder.read_next_frame().ok()? {
+        delays.push(frame_delay(Duration::from_millis(frame.delay as u64 * 10)));
+    }
+    // NETSCAPE kiterjesztés: 0 végtelen, n: n további ismétlés; ha nincs, egyszer játszik
+    let plays = match decoder.repeat() {
+        gif::Repeat::Infinite => 0,
+        gif::Repeat::Finite(n) => n as u32 + 1,
+    };
+    Some(AnimTiming { delays, plays })
+}
+
+fn webp_timing(mut reader: BufReader<File>) -> Option<AnimTiming> {
+    let mut header = [0u8; 12];
+    reader.read_exact(&mut header).ok()?;
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WEBP" {
+        return None;
+    }
+    // RIFF chunkok: FourCC, méret (LE), adat páros hosszra kiegészítve
+    let mut delays = Vec::new();
+    let mut plays = 0;
+    let mut chunk = [0u8; 8];
+    while reader.read_exact(&mut chunk).is_ok() {
+        let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as i64;
+        let mut skip = size + (size & 1);
+        match &chunk[0..4] {
+            b"ANIM" => {
+                // háttérszín (4 bájt), majd az ismétlésszám (0: végtelen)
+                let mut anim = [0u8; 6];
+                reader.read_exact(&mut anim).ok()?;
+                plays = u16::from_le_bytes([anim[4], anim[5]]) as u32;
+                skip -= 6;
+            }
+            b"ANMF" => {
+                // X, Y, szélesség-1, magasság-1 (3-3 bájt), majd 3 bájtos időtartam ms-ban
+                let mut frame = [0u8; 16];
+                reader.read_exact(&mut frame).ok()?;
+                let ms = u32::from_le_bytes([frame[12], frame[13], frame[14], 0]);
+                delays.push(frame_delay(Duration::from_millis(ms as u64)));
+                skip -= 16;
+            }
+            _ => {}
+        }
+        reader.seek(SeekFrom::Current(skip)).ok()?;
+    }
+    Some(AnimTiming { delays, plays })
+}
+
+fn apng_timing(mut reader: BufReader<File>) -> Option<AnimTiming> {
+    let mut signature = [0u8; 8];
+    reader.read_exact(&mut signature).ok()?;
+    if signature != [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A] {
+        return None;
+    }
+    // PNG chunkok: hossz (BE), típus, adat, CRC; az acTL az első IDAT előtt áll
+    let mut animated = false;
+    let mut plays = 0;
+    let mut delays = Vec::new();
+    let mut chunk = [0u8; 8];
+    while reader.read_exact(&mut chunk).is_ok() {
+        let size = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) as i64;
+        let mut skip = size + 4;
+        match &chunk[4..8] {
+            b"acTL" => {
+                // képkockák száma, lejátszások száma (0: végtelen)
+                let mut control = [0u8; 8];
+                reader.read_exact(&mut control).ok()?;
+                plays = u32::from_be_bytes([control[4], control[5], control[6], control[7]]);
+                animated = true;
+                skip -= 8;
+            }
+            b"IDAT" if !animated => return None,
+            b"IEND" => break,
+            b"fcTL" => {
+                // sorszám, méret, eltolás (4-4 bájt), majd a késleltetés számláló és nevező
+                let mut control = [0u8; 26];
+                reader.read_exact(&mut control).ok()?;
+                let num = u16::from_be_bytes([control[20], control[21]]) as u32;
+                let den = match u16::from_be_bytes([control[22], control[23]]) {
+                    0 => 100,
+                    den => den as u32,
+                };
+                delays.push(frame_delay(Duration::from_micros(num as u64 * 1_000_000 / den as u64)));
+                skip -= 26;
+            }
+            _ => {}
+        }
+        reader.seek(SeekFrom::Current(skip)).ok()?;
+    }
+    animated.then_some(AnimTiming { delays, plays })
+}
+
+struct StreamState {
+    frames: HashMap<usize, Arc<DynamicImage>>,
+    wanted: usize,   // a lejátszott / kért képkocka, az ablak ehhez igazodik
+    capacity: usize, // az ablak mérete képkockában
+    reverse: bool,   // visszafelé halad a lejátszás
+    closed: bool,
+    failed: Option<String>, // a dekódolás hibája; utána több képkocka nem jön
+}
+
+struct StreamShared {
+    state: Mutex<StreamState>,
+    changed: Condvar,
+    total: usize,
+}
+
+/// Előre a kapacitás háromnegyede, visszafelé a negyede; ha minden belefér, az egész.
+fn window_ahead(capacity: usize, total: usize) -> usize {
+    if capacity >= total { total } else { capacity - capacity / 4 }
+}
+
+fn in_window(i: usize, wanted: usize, capacity: usize, reverse: bool, total: usize) -> bool {
+    if capacity >= total {
+        return true;
+    }
+    let mut forward = (i + total - wanted) % total;
+    let mut backward = (wanted + total - i) % total;
+    if reverse {
+        std::mem::swap(&mut forward, &mut backward);
+    }
+    forward < window_ahead(capacity, total) || backward <= capacity / 4
+}
+
+impl StreamState {
+    /// A kért kockától a lejátszás irányában a d. képkocka (körbe).
+    fn step(&self, d: usize, total: usize) -> usize {
+        if self.reverse { (self.wanted + total - d % total) % total } else { (self.wanted + d) % total }
+    }
+
+    /// A következő dekódolandó képkocka; None, ha az ablak rendben van.
+    /// Előre az első hiányzó a kérttől; visszafelé (a dekóder csak előre halad) csak akkor tölt,
+    /// ha a következő néhány kocka hiányzik, és akkor a dekódolási sorrendben legelső hiányzóval kezd.
+    fn next_target(&self, total: usize) -> Option<usize> {
+        let ahead = window_ahead(self.capacity, total);
+        let missing = |i: &usize| !self.frames.contains_key(i);
+        if !self.reverse {
+            return (0..ahead).map(|d| self.step(d, total)).find(missing);
+        }
+        let margin = (MIN_WINDOW_FRAMES / 2).min(ahead);
+        if !(0..margin).map(|d| self.step(d, total)).any(|i| missing(&i)) {
+            return None;
+        }
+        (0..ahead).rev().map(|d| self.step(d, total)).find(missing)
+    }
+}
+
+struct StreamHandle {
+    shared: Arc<StreamShared>,
+    source: FrameSource,
+}
+
+impl Drop for StreamHandle {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.shared.state.lock() {
+            state.closed = true;
+        }
+        self.shared.changed.notify_all();
+    }
+}
+
+/// A képkockák csúszó ablaka; a háttérszál az utolsó példány eldobásakor leáll.
+#[derive(Clone)]
+pub struct FrameStream {
+    handle: Arc<StreamHandle>,
+}
+
+impl FrameStream {
+
+    /// A háttérszál indítása; az ablak window_bytes memóriát foglalhat (de legalább 8 kockát).
+    pub fn start(source: FrameSource, total: usize, window_bytes: usize) -> Self {
+        let shared = Arc::new(StreamShared {
+            state: Mutex::new(StreamState {
+                frames: HashMap::new(),
+                wanted: 0,
+                capacity: MIN_WINDOW_FRAMES,
+                reverse: false,
+                closed: false,
+                failed: None,
+            }),
+            changed: Condvar::new(),
+            total,
+        });
+        let worker_shared = shared.clone();
+        let worker_source = source.clone();
+        std::thread::spawn(move || decode_worker(worker_shared, worker_source, window_bytes));
+        Self { handle: Arc::new(StreamHandle { shared, source }) }
+    }
+
+    /// A kért képkocka, ha már kész; egyúttal az ablakot is ide mozdítja.
+    pub fn frame(&self, index: usize) -> Option<Arc<DynamicImage>> {
+        let shared = &self.handle.shared;
+        let mut state = shared.state.lock().ok()?;
+        if state.wanted != index {
+            // egy lépés hátra (körbe is) visszafelé, egy lépés előre előre fordítja az ablakot
+            let total = shared.total;
+            if (index + 1) % total == state.wanted {
+                state.reverse = true;
+            } else if (state.wanted + 1) % total == index {
+                state.reverse = false;
+            }
+            state.wanted = index;
+            shared.changed.notify_all();
+        }
+        state.frames.get(&index).cloned()
+    }
+
+    /// A képkocka, ha az ablakban van; az ablakot nem mozdítja (szomszédos kockákhoz).
+    pub fn peek(&self, index: usize) -> Option<Arc<DynamicImage>> {
+        self.handle.shared.state.lock().ok()?.frames.get(&index).cloned()
+    }
+
+    /// Vár a képkockára (betöltő szálon); None, ha a dekódolás hibával leállt.
+    pub fn wait_frame(&self, index: usize) -> Option<Arc<DynamicImage>> {
+        let shared = &self.handle.shared;
+        let mut state = shared.state.lock().ok()?;
+        state.wanted = index;
+        shared.changed.notify_all();
+        loop {
+            if let Some(frame) = state.frames.get(&index) {
+                return Some(frame.clone());
+            }
+            if state.failed.is_some() || state.closed {
+                return None;
+            }
+            state = shared.changed.wait(state).ok()?;
+        }
+    }
+
+    /// A fájlban lévő képkockák száma.
+    pub fn total(&self) -> usize {
+        self.handle.shared.total
+    }
+
+    pub fn failed(&self) -> bool {
+        self.handle.shared.state.lock().map_or(true, |state| state.failed.is_some())
+    }
+
+    /// A háttérszál dekódolási hibája, ha leállt.
+    pub fn error(&self) -> Option<String> {
+        self.handle.shared.state.lock().ok()?.failed.clone()
+    }
+
+    /// Az ablak képkockáinak memóriája; a `shared` képet nem számolja kétszer.
+    pub fn bytes(&self, shared: Option<&Arc<DynamicImage>>) -> usize {
+        self.handle.shared.state.lock().map_or(0, |state| {
+            state.frames.values()
+                .filter(|f| !shared.is_some_and(|s| Arc::ptr_eq(f, s)))
+                .map(|f| f.as_bytes().len())
+                .sum()
+        })
+    }
+
+    /// A fájl, amiből a képkockák jönnek (a háttérszálas bélyegképekhez).
+    pub fn source(&self) -> FrameSource {
+        self.handle.source.clone()
+    }
+}
+
+fn decode_worker(shared: Arc<StreamShared>, source: FrameSource, window_bytes: usize) {
+    let total = shared.total;
+    let mut frames: Option<image::Frames<'static>> = None;
+    let mut next = 0;
+    let mut sized = false;
+    loop {
+        let target = {
+            let Ok(mut state) = shared.state.lock() else {
+                return;
+            };
+            loop {
+                if state.closed {
+                    return;
+                }
+                if let Some(target) = state.next_target(total) {
+                    break target;
+                }
+                state = match shared.changed.wait(state) {
+                    Ok(state) => state,
+                    Err(_) => return,
+                };
+            }
+        };
+        // visszafelé csak elölről lehet dekódolni
+        if target < next || frames.is_none() {
+            match open_frames(&source) {
+                Ok(opened) => frames = Some(opened),
+                Err(e) => return stream_failed(&shared, &e),
+            }
+            next = 0;
+        }
+        let image = match frames.as_mut().and_then(|f| f.next()) {
+            Some(Ok(frame)) => Arc::new(DynamicImage::ImageRgba8(frame.into_buffer())),
+            Some(Err(e)) => return stream_failed(&shared, &e.to_string()),
+            None => return stream_failed(&shared, "The animation has fewer frames than its header"),
+        };
+        let Ok(mut state) = shared.state.lock() else {
+            return;
+        };
+        if !sized {
+            sized = true;
+            state.capacity = (window_bytes / image.as_bytes().len().max(1)).max(MIN_WINDOW_FRAMES);
+        }
+        let (wanted, capacity, reverse) = (state.wanted, state.capacity, state.reverse);
+        if in_window(next, wanted, capacity, reverse, total) {
+            state.frames.insert(next, image);
+        }
+        state.frames.retain(|i, _| in_window(*i, wanted, capacity, reverse, total));
+        drop(state);
+        shared.changed.notify_all();
+        next += 1;
+    }
+}
+
+fn stream_failed(shared: &StreamShared, error: &str) {
+    if let Ok(mut state) = shared.state.lock() {
+        state.failed = Some(error.to_string());
+    }
+    shared.changed.notify_all();
+}
+
+impl ImageViewer {
+
+    /// A képkocka megjelenítése; ha a háttérszál még nem készült el vele, kicsit később újra próbálja.
+    pub fn show_frame(&mut self, frame: usize) {
+        self.current_frame = frame;
+        let Some(anim) = &self.anim_data else {
+            return;
+        };
+        if let Some(image) = anim.frame(frame) {
+            // a cache a fájlbeli sorszámot használja, így a szerkesztés nem érvényteleníti
+            let source = anim.source_index(frame);
+            self.set_original_image(image);
+            self.frame_cache.shown = source;
+            self.review(true, false);
+        } else if anim.frames.failed() {
+            let error = anim.frames.error().unwrap_or_default();
+            self.anim_playing = false;
+            self.sync_timeline();
+            if let Some(path) = &self.shown_image {
+                self.notify_error(&ViewerError::load(path, error));
+            }
+        } else if let Some(state_rc) = self.self_weak.clone() {
+            self.anim_timer.start(
+                slint::TimerMode::SingleShot,
+                Duration::from_millis(15),
+                move || {
+                    let mut v = state_rc.borrow_mut();
+                    let current = v.current_frame;
+                    v.show_frame(current);
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_temp(name: &str, data: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("iviewer_{}_{}", std::process::id(), name));
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    fn ms(timing: &AnimTiming) -> Vec<u64> {
+        timing.delays.iter().map(|d| d.as_millis() as u64).collect()
+    }
+
+    fn riff_chunk(fourcc: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = fourcc.to_vec();
+        chunk.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    /// ANMF: X, Y, szélesség-1, magasság-1, időtartam (3-3 bájt), jelzők, majd a képadat.
+    fn anmf(duration_ms: u32) -> Vec<u8> {
+        let mut data = vec![0u8; 12];
+        data.extend_from_slice(&duration_ms.to_le_bytes()[..3]);
+        data.push(0);
+        data.extend_from_slice(&riff_chunk(b"VP8L", &[0x2f, 0, 0, 0, 0]));
+        riff_chunk(b"ANMF", &data)
+    }
+
+    fn png_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(kind);
+        chunk.extend_from_slice(data);
+        chunk.extend_from_slice(&[0; 4]); // CRC, az időzítés olvasása nem ellenőrzi
+        chunk
+    }
+
+    /// fcTL: sorszám, méret, eltolás (4-4 bájt), késleltetés számláló és nevező, dispose, blend.
+    fn fctl(sequence: u32, num: u16, den: u16) -> Vec<u8> {
+        let mut data = sequence.to_be_bytes().to_vec();
+        data.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0]);
+        data.extend_from_slice(&num.to_be_bytes());
+        data.extend_from_slice(&den.to_be_bytes());
+        data.extend_from_slice(&[0, 0]);
+        png_chunk(b"fcTL", &data)
+    }
+
+    #[test]
+    fn webp_timing_reads_loop_count_and_durations() {
+        let mut body = b"WEBP".to_vec();
+        body.extend_from_slice(&riff_chunk(b"VP8X", &[0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0]));
+        body.extend_from_slice(&riff_chunk(b"ANIM", &[0, 0, 0, 0, 3, 0]));
+        body.extend_from_slice(&anmf(40));
+        body.extend_from_slice(&anmf(0));
+        body.extend_from_slice(&anmf(0x01_0000 + 250));
+        let mut data = b"RIFF".to_vec();
+        data.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        data.extend_from_slice(&body);
+        let path = write_temp("timing.webp", &data);
+
+        let timing = read_timing(&path, SaveFormat::Webp).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(timing.plays, 3);
+        // a 0 késleltetés 100 ms, az időtartam 3 bájtos
+        assert_eq!(ms(&timing), vec![40, 100, 65_786]);
+    }
+
+    #[test]
+    fn apng_timing_reads_loop_count_and_fractional_delays() {
+        let mut data = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        data.extend_from_slice(&png_chunk(b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]));
+        data.extend_from_slice(&png_chunk(b"acTL", &[0, 0, 0, 3, 0, 0, 0, 2]));
+        data.extend_from_slice(&fctl(0, 1, 25));
+        data.extend_from_slice(&png_chunk(b"IDAT", &[0; 8]));
+        data.extend_from_slice(&fctl(1, 7, 0));
+        data.extend_from_slice(&png_chunk(b"fdAT", &[0; 8]));
+        data.extend_from_slice(&fctl(3, 0, 10));
+        data.extend_from_slice(&png_chunk(b"fdAT", &[0; 8]));
+        data.extend_from_slice(&png_chunk(b"IEND", &[]));
+        let path = write_temp("timing.png", &data);
+
+        let timing = read_timing(&path, SaveFormat::Png).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(timing.plays, 2);
+        // 1/25 s; 0 nevező: század másodperc; 0 számláló: 100 ms
+        assert_eq!(ms(&timing), vec![40, 70, 100]);
+    }
+
+    #[test]
+    fn apng_timing_ignores_still_png() {
+        let mut data = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        data.extend_from_slice(&png_chunk(b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]));
+        data.extend_from_slice(&png_chunk(b"IDAT", &[0; 8]));
+        data.extend_from_slice(&png_chunk(b"IEND", &[]));
+        let path = write_temp("still.png", &data);
+
+        let timing = read_timing(&path, SaveFormat::Png);
+        let _ = std::fs::remove_file(&path);
+        assert!(timing.is_none());
+    }
+
+    #[test]
+    fn for_each_in_order_follows_the_edited_order() {
+        let path = std::env::temp_dir().join(format!("iviewer_{}_order.gif", std::process::id()));
+        let mut writer = crate::anim_save::AnimWriter::gif(&path, 0).unwrap();
+        for red in [0u8, 60, 120, 180] {
+            writer.add_frame(image::RgbaImage::from_pixel(4, 4, image::Rgba([red, 0, 0, 255])), Duration::from_millis(50)).unwrap();
+        }
+        writer.finish().unwrap();
+        let source = FrameSource { path: path.clone(), format: SaveFormat::Gif, limits: image::Limits::default() };
+        let mut seen = Vec::new();
+        source.for_each_in_order(&[3, 1, 1, 0, 2], |i, frame| {
+            seen.push((i, frame.to_rgba8().get_pixel(0, 0)[0]));
+            Ok(())
+        }).unwrap();
+        assert_eq!(seen, vec![(0, 180), (1, 60), (2, 60), (3, 0), (4, 120)]);
+        assert!(source.for_each_in_order(&[5], |_, _| Ok(())).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,307 @@
+// Animáció összeállítása a mappa képeiből (vagy a kijelölt fájlokból) a rendezés sorrendjében:
+// időzített előnézetekhez. Közös késleltetés vagy képkockánkénti lista, opcionális közös méret,
+// és az aktuális színkorrekció. A dekódolás és a kódolás háttérszálon fut, az ablak mutatja a haladást.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use image::{DynamicImage, RgbaImage};
+use slint::ComponentHandle;
+
+use crate::ImageViewer;
+use crate::colors::*;
+use crate::errors::*;
+use crate::image_processing::rotate_and_color;
+use crate::image_loader::{decode_error_text, read_exif};
+use crate::file_handlers::{detect_format, sort_key, SaveFormat, SUPPORTED_EXTENSIONS};
+use crate::modern_formats::decode_jxl;
+use crate::raw_image::decode_raw;
+use crate::anim_save::AnimWriter;
+use crate::atomic_save::AtomicFile;
+
+/// Egy összeállítás minden adata; a háttérszál ezzel dolgozik.
+struct AssembleJob {
+    files: Vec<PathBuf>,
+    delays: Vec<Duration>,
+    fit: Option<(u32, u32)>, // közös méret (ebbe a dobozba kicsinyít / nagyít, az arány marad)
+    colset: ColorSettings,
+    webp: bool,
+    quality: f32,
+    plays: u32, // 0: végtelen
+    limits: image::Limits,
+    target: PathBuf,
+    keep_backup: bool,
+}
+
+impl ImageViewer {
+
+    /// Az összeállító ablak megnyitása az aktuális mappával.
+    pub fn open_assemble(&mut self) {
+        self.make_image_list();
+        self.refresh_assemble();
+        if let Some(a_ui) = &self.assemble_window {
+            a_ui.show().unwrap();
+        }
+    }
+
+    /// A forrás képek a rendezés (SortDir) sorrendjében.
+    fn assemble_sources(&self) -> Vec<PathBuf> {
+        let selected = self.assemble_window.as_ref().is_some_and(|a_ui| a_ui.get_selected());
+        if selected {
+            let mut files = self.assemble_files.clone();
+            files.sort_by_cached_key(|path| sort_key(self.sort, path));
+            files
+        } else {
+            self.list_of_images.iter().map(|entry| entry.path()).collect()
+        }
+    }
+
+    pub fn refresh_assemble(&self) {
+        let Some(a_ui) = &self.assemble_window else {
+            return;
+        };
+        let count = self.assemble_sources().len();
+        let text = if a_ui.get_selected() {
+            format!("{} selected images, sorted by {:?}", count, self.sort)
+        } else {
+            let folder = self.image_folder.as_ref()
+                .and_then(|p| p.file_name())
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            format!("{} images in folder {}, sorted by {:?}", count, folder, self.sort)
+        };
+        a_ui.set_source_text(text.into());
+    }
+
+    /// A mappa egy részének kijelölése.
+    pub fn select_assemble_files(&mut self) {
+        let mut dialog = rfd::FileDialog::new().add_filter("Images", &SUPPORTED_EXTENSIONS);
+        if let Some(folder) = &self.image_folder {
+            dialog = dialog.set_directory(folder);
+        }
+        let Some(files) = dialog.pick_files() else {
+            return;
+        };
+        self.assemble_files = files;
+        if let Some(a_ui) = &self.assemble_window {
+            a_ui.set_selected(true);
+        }
+        self.refresh_assemble();
+    }
+
+    pub fn assemble_whole_folder(&mut self) {
+        self.assemble_files.clear();
+        if let Some(a_ui) = &self.assemble_window {
+            a_ui.set_selected(false);
+        }
+        self.refresh_assemble();
+    }
+
+    /// A cél fájl bekérése és az összeállítás indítása háttérszálon.
+    pub fn assemble_animation(&mut self) {
+        let Some(a_ui) = &self.assemble_window else {
+            return;
+        };
+        let files = self.assemble_sources();
+        if files.is_empty() {
+            a_ui.set_status("No images to assemble".into());
+            return;
+        }
+        let delays = match frame_delays(a_ui.get_delay_ms(), a_ui.get_delays().as_str(), files.len()) {
+            Ok(delays) => delays,
+            Err(e) => {
+                a_ui.set_status(e.into());
+                return;
+            }
+        };
+        let webp = a_ui.get_webp();
+        let ext = if webp { "webp" } else { "gif" };
+        let name = self.image_folder.as_ref()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "animation".to_string());
+        let mut dialog = rfd::FileDialog::new()
+            .add_filter(ext.to_uppercase(), &[ext])
+            .set_file_name(format!("{}.{}", name, ext));
+        if let Some(folder) = &self.image_folder {
+            dialog = dialog.set_directory(folder);
+        }
+        let Some(target) = dialog.save_file() else {
+            return;
+        };
+        let job = AssembleJob {
+            files,
+            delays,
+            fit: if a_ui.get_resize() {
+                Some((a_ui.get_fit_width().max(1) as u32, a_ui.get_fit_height().max(1) as u32))
+            } else {
+                None
+            },
+            colset: self.effective_color_settings(),
+            webp,
+            quality: a_ui.get_quality(),
+            plays: if a_ui.get_loop_play() { 0 } else { 1 },
+            limits: self.config.decode_limits(),
+            target,
+            keep_backup: self.config.keep_backup,
+        };
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.assemble_cancel = Some(cancel.clone());
+        a_ui.set_busy(true);
+        let weak = a_ui.as_weak();
+        std::thread::spawn(move || {
+            let status = |text: String, busy: bool| {
+                let weak = weak.clone();
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(a_ui) = weak.upgrade() {
+                        a_ui.set_status(text.into());
+                        a_ui.set_busy(busy);
+                    }
+                });
+            };
+            let result = run_assemble(&job, &cancel, |i| status(format!("Frame {} / {} ...", i + 1, job.files.len()), true));
+            match result {
+                Ok(text) => status(text, false),
+                Err(e) => status(e.to_string().replace('\n', ": "), false),
+            }
+        });
+    }
+
+    pub fn cancel_assemble(&mut self) {
+        if let Some(cancel) = &self.assemble_cancel {
+            cancel.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+/// A képkockák késleltetése: a lista elemei sorban, az utolsó ismétlődik; üres lista: egységes.
+fn frame_delays(uniform_ms: i32, list: &str, count: usize) -> Result<Vec<Duration>, String> {
+    let mut delays = Vec::new();
+    for item in list.split([',', ';', ' ']).filter(|s| !s.is_empty()) {
+        let ms: u64 = item.parse().map_err(|_| format!("Invalid delay: {}", item))?;
+        delays.push(Duration::from_millis(ms.max(10)));
+    }
+    let last = delays.last().copied().unwrap_or(Duration::from_millis(uniform_ms.max(10) as u64));
+    delays.resize(count, last);
+    Ok(delays)
+}
+
+/// Egy forrás kép dekódolása az EXIF Orientation szerint elforgatva.
+fn decode_frame(path: &Path, limits: &image::Limits) -> Result<DynamicImage, String> {
+    let format = detect_format(path)?;
+    let mut img = match format {
+        SaveFormat::Jxl => decode_jxl(path, limits.clone())?,
+        SaveFormat::Raw => decode_raw(path, false, limits)?.image,
+        _ => {
+            let mut reader = image::ImageReader::open(path)
+                .and_then(|r| r.with_guessed_format())
+                .map_err(|e| e.to_string())?;
+            reader.limits(limits.clone());
+            reader.decode().map_err(|e| decode_error_text(&e, limits))?
+        }
+    };
+    read_exif(&path.to_path_buf(), format, &mut img, &mut None);
+    Ok(img)
+}
+
+/// A kép a vászon közepére, az arányt megtartva; a maradék átlátszó.
+fn fit_canvas(frame: RgbaImage, width: u32, height: u32) -> RgbaImage {
+    if frame.dimensions() == (width, height) {
+        return frame;
+    }
+    let frame = DynamicImage::ImageRgba8(frame)
+        .resize(width, height, image::imageops::FilterType::Triangle)
+        .to_rgba8();
+    let mut canvas = RgbaImage::new(width, height);
+    let x = (width - frame.width()) / 2;
+    let y = (height - frame.height()) / 2;
+    image::imageops::overlay(&mut canvas, &frame, x as i64, y as i64);
+    canvas
+}
+
+/// Dekódolás, méretezés, színkorrekció és kódolás; a nem olvasható képek kimaradnak.
+fn run_assemble(job: &AssembleJob, cancel: &AtomicBool, progress: impl Fn(usize)) -> ViewerResult<String> {
+    let save_err = |e: &dyn std::fmt::Display| ViewerError::save(&job.target, e);
+    let lut = if job.colset.is_setted() || job.colset.is_blured() {
+        let mut lut = Lut4ColorSettings::default();
+        lut.update_lut(&job.colset);
+        Some(lut)
+    } else {
+        None
+    };
+    // 90 / 270 fokos forgatásnál a doboz a forgatás előtti képre fordítva értendő
+    let turned = matches!(job.colset.rotate, Rotate::Rotate90 | Rotate::Rotate270);
+    let mut canvas: Option<(u32, u32)> = job.fit;
+    // a képkockák egyenként mennek a kódolóba, így egyszerre csak egy van a memóriában
+    let atomic = AtomicFile::new(&job.target);
+    let mut writer = if job.webp {
+        AnimWriter::webp(atomic.temp_path(), job.plays, job.quality, false)
+    } else {
+        AnimWriter::gif(atomic.temp_path(), job.plays)
+    }.map_err(|e| save_err(&e))?;
+    let mut count = 0;
+    let mut skipped = 0;
+    for (i, path) in job.files.iter().enumerate() {
+        if cancel.load(Ordering::SeqCst) {
+            return Err(save_err(&"cancelled"));
+        }
+        progress(i);
+        let img = match decode_frame(path, &job.limits) {
+            Ok(img) => img,
+            Err(_) => {
+                skipped += 1;
+                continue;
+            }
+        };
+        // előbb kicsinyít, utána színez: nagy fotóknál így a LUT gyors
+        let img = match job.fit {
+            Some((w, h)) if turned => img.resize(h, w, image::imageops::FilterType::Lanczos3),
+            Some((w, h)) => img.resize(w, h, image::imageops::FilterType::Lanczos3),
+            None => img,
+        };
+        let rgba = rotate_and_color(&img, &job.colset, lut.as_ref());
+        // méretezés nélkül az első kép mérete a vászon
+        let (w, h) = *canvas.get_or_insert(rgba.dimensions());
+        writer.add_frame(fit_canvas(rgba, w, h), job.delays[i]).map_err(|e| save_err(&e))?;
+        count += 1;
+    }
+    if count == 0 {
+        return Err(save_err(&"None of the images could be read"));
+    }
+    writer.finish().map_err(|e| save_err(&e))?;
+    atomic.commit(job.keep_backup).map_err(|e| save_err(&e))?;
+    let mut text = format!("{} frames saved to {}", count, job.target.display());
+    if skipped > 0 {
+        text += &format!(" ({} unreadable images skipped)", skipped);
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(delays: &[Duration]) -> Vec<u64> {
+        delays.iter().map(|d| d.as_millis() as u64).collect()
+    }
+
+    #[test]
+    fn frame_delays_uniform_without_list() {
+        assert_eq!(ms(&frame_delays(100, "", 3).unwrap()), vec![100, 100, 100]);
+        assert_eq!(ms(&frame_delays(0, " ", 2).unwrap()), vec![10, 10]);
+    }
+
+    #[test]
+    fn frame_delays_repeat_the_last_item() {
+        assert_eq!(ms(&frame_delays(100, "50, 70", 4).unwrap()), vec![50, 70, 70, 70]);
+        assert_eq!(ms(&frame_delays(100, "20;30 40,5", 5).unwrap()), vec![20, 30, 40, 10, 10]);
+        assert_eq!(ms(&frame_delays(100, "20,30,40", 2).unwrap()), vec![20, 30]);
+    }
+
+    #[test]
+    fn frame_delays_reject_invalid_items() {
+        assert_eq!(frame_delays(100, "50,abc", 3), Err("Invalid delay: abc".to_string()));
+        assert!(frame_delays(100, "-20", 1).is_err());
+    }
+}
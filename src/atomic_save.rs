@@ -0,0 +1,146 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::errors::*;
+
+// Felülírás-biztos mentés: a kép előbb egy ugyanabban a könyvtárban lévő ideiglenes fájlba kerül,
+// lemezre íródik (fsync), és csak utána nevezzük át a cél helyére. Ha a kódolás vagy az írás
+// közben hiba történik, az eredeti fájl érintetlen marad, az ideiglenes fájl pedig törlődik.
+
+pub struct AtomicFile {
+    target: PathBuf,
+    temp: PathBuf,
+    committed: bool,
+}
+
+impl AtomicFile {
+    pub fn new(target: &Path) -> Self {
+        let dir = target.parent().unwrap_or(Path::new("."));
+        let name = target.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let temp = dir.join(format!(".{}.{}.tmp", name, std::process::id()));
+        Self { target: target.to_path_buf(), temp, committed: false }
+    }
+
+    /// Ide kell írni a mentés tartalmát.
+    pub fn temp_path(&self) -> &Path {
+        &self.temp
+    }
+
+    /// A felülírt fájl biztonsági másolatának neve: `kep.jpg` -> `kep.jpg.bak`.
+    pub fn backup_path(target: &Path) -> PathBuf {
+        let mut name = target.as_os_str().to_os_string();
+        name.push(".bak");
+        PathBuf::from(name)
+    }
+
+    /// Az ideiglenes fájl lemezre írása és átnevezése a cél helyére; `backup` esetén
+    /// a meglévő célfájl `.bak` néven megmarad.
+    pub fn commit(mut self, backup: bool) -> io::Result<()> {
+        // Windows-on a FlushFileBuffers írási jogot kér, csak olvasásra nyitva "access denied"
+        fs::OpenOptions::new().write(true).open(&self.temp)?.sync_all()?;
+        let moved = if backup && self.target.is_file() {
+            Self::make_backup(&self.target)?
+        } else {
+            false
+        };
+        if let Err(e) = fs::rename(&self.temp, &self.target) {
+            // az átnevezett eredeti visszakerül, ne csak a .bak maradjon
+            if moved {
+                let _ = fs::rename(Self::backup_path(&self.target), &self.target);
+            }
+            return Err(e);
+        }
+        self.committed = true;
+        sync_dir(&self.target);
+        Ok(())
+    }
+
+    /// A `.bak` hard link a célra, így másolás nélkül, egy lépésben készül, és a cél a helyén marad.
+    /// Ahol a fájlrendszer nem ismeri (pl. FAT), a cél átnevezése; ekkor true.
+    fn make_backup(target: &Path) -> io::Result<bool> {
+        let backup = Self::backup_path(target);
+        if backup.is_file() {
+            fs::remove_file(&backup)?;
+        }
+        if fs::hard_link(target, &backup).is_ok() {
+            return Ok(false);
+        }
+        fs::rename(target, &backup)?;
+        Ok(true)
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.temp);
+        }
+    }
+}
+
+/// Oldal / képkocka exportja PNG-be, ugyanazzal a felülírás-biztos módszerrel.
+pub fn save_png(img: &image::DynamicImage, target: &Path) -> ViewerResult<()> {
+    let atomic = AtomicFile::new(target);
+    img.save_with_format(atomic.temp_path(), image::ImageFormat::Png)
+        .map_err(|e| ViewerError::save(target, e))?;
+    atomic.commit(false).map_err(|e| ViewerError::save(target, e))
+}
+
+/// Az átnevezés is kerüljön lemezre (Unix); Windows-on a könyvtár nem nyitható meg így.
+fn sync_dir(target: &Path) {
+    #[cfg(unix)]
+    if let Some(dir) = target.parent() {
+        let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+        if let Ok(d) = fs::File::open(dir) {
+            let _ = d.sync_all();
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = target;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("iviewer_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn commit_replaces_target_and_keeps_backup() {
+        let dir = temp_dir("atomic_backup");
+        let target = dir.join("kep.jpg");
+        fs::write(&target, b"old").unwrap();
+        fs::write(AtomicFile::backup_path(&target), b"older").unwrap();
+
+        let atomic = AtomicFile::new(&target);
+        fs::write(atomic.temp_path(), b"new").unwrap();
+        let temp = atomic.temp_path().to_path_buf();
+        atomic.commit(true).unwrap();
+
+        assert_eq!(fs::read(&target).unwrap(), b"new");
+        assert_eq!(fs::read(AtomicFile::backup_path(&target)).unwrap(), b"old");
+        assert!(!temp.exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn dropped_without_commit_leaves_target_untouched() {
+        let dir = temp_dir("atomic_drop");
+        let target = dir.join("kep.png");
+        fs::write(&target, b"old").unwrap();
+        let temp = {
+            let atomic = AtomicFile::new(&target);
+            fs::write(atomic.temp_path(), b"half").unwrap();
+            atomic.temp_path().to_path_buf()
+        };
+        assert!(!temp.exists());
+        assert_eq!(fs::read(&target).unwrap(), b"old");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -43,7 +43,7 @@ fn r(th: f32) -> f32 {
     (ra * rb) / (rb * th.cos()).hypot(ra * th.sin())
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
 pub struct ColorSettings {
     //pub is_default: bool,
     pub gamma: f32,
@@ -229,12 +229,20 @@ impl ColorSettings {
 
 }
 
+/// A párhuzamos feldolgozás egységei: ennyi soronként kap egy-egy szál munkát.
+const LUT_BAND_ROWS: usize = 32;
+
+/// Élesítésnél a sáv legalább ennyiszer magasabb a sugárnál: a sáv fölötti és alatti
+/// r-r sor vízszintes menetét a szomszéd sáv is kiszámolja, így ez a többlet legfeljebb 25%.
+const SHARPEN_BAND_PER_RADIUS: usize = 8;
+
 #[derive(Clone, Debug)]
 pub struct Lut4ColorSettings {
     pub size : usize,
     pub data : Vec<u8>, // RGBA adatok
     pub sharpen_amount: f32, // -1.0 .. 5.0 // realy image setting
     pub sharpen_radius: f32, // 0.2 .. 3.0 // realy image setting
+    built_for: Option<ColorSettings>, // ezekkel a beállításokkal készült a tábla
 }
 
 impl Lut4ColorSettings {
@@ -256,7 +264,7 @@ impl Lut4ColorSettings {
                 }
             }
         }
-        Self { size:size, data:data, sharpen_amount:0.0,sharpen_radius:0.0, }
+        Self { size, data, sharpen_amount:0.0,sharpen_radius:0.0, built_for: None, }
     }
     
     pub fn default() -> Lut4ColorSettings {
@@ -266,6 +274,11 @@ impl Lut4ColorSettings {
     }
 
     pub fn update_lut(&mut self, colset: &ColorSettings) {
+        // változatlan beállításnál (pl. animáció minden képkockájánál) nem számol újra
+        if self.built_for.as_ref() == Some(colset) {
+            return;
+        }
+        self.built_for = Some(*colset);
         let mut idx = 0;
         for b in 0..self.size {
             for g in 0..self.size {
@@ -287,142 +300,139 @@ impl Lut4ColorSettings {
         self.sharpen_radius = colset.sharpen_radius;
     }
     
-    pub fn apply_lut_pixel(&self, _x: u32, _y: u32, pix: & mut image::Rgba<u8>) {
-        
-        let parts = 256 / (self.size-1); // 8
-        
-        let red_0 = (pix[0] as usize) / parts;          // 0 - 31
-        let red_1 =  red_0 + 1;                         // 1 - 32
-        let red_a = (pix[0] as usize) - red_0 * parts;  // 0 - 7
-        let red_b = parts - 1 - red_a;                  // 7 - 0
-
-        let gre_0 = (pix[1] as usize) / parts;
-        let gre_1 =  gre_0 + 1;
-        let gre_a = (pix[1] as usize) - gre_0 * parts;
-        let gre_b = parts - 1 - gre_a;
-        
-        let blu_0 = (pix[2] as usize) / parts;
-        let blu_1 =  blu_0 + 1;
-        let blu_a = (pix[2] as usize) - blu_0 * parts;
-        let blu_b = parts - 1 - blu_a;
-        
-        let idx000  = ((((blu_0 * self.size) + gre_0)  * self.size) + red_0) * 4;
-        let idx001  = ((((blu_0 * self.size) + gre_0)  * self.size) + red_1) * 4;
-        let idx010  = ((((blu_0 * self.size) + gre_1)  * self.size) + red_0) * 4;
-        let idx011  = ((((blu_0 * self.size) + gre_1)  * self.size) + red_1) * 4;
-        let idx100  = ((((blu_1 * self.size) + gre_0)  * self.size) + red_0) * 4;
-        let idx101  = ((((blu_1 * self.size) + gre_0)  * self.size) + red_1) * 4;
-        let idx110  = ((((blu_1 * self.size) + gre_1)  * self.size) + red_0) * 4;
-        let idx111  = ((((blu_1 * self.size) + gre_1)  * self.size) + red_1) * 4;
-        
-        let div = (parts-1)*(parts-1)*(parts-1);
-        pix[0] = ( (self.data[idx000  ]as usize * (red_b*gre_b*blu_b) +
-                    self.data[idx001  ]as usize * (red_b*gre_b*blu_a) +
-                    self.data[idx010  ]as usize * (red_b*gre_a*blu_b) +
-                    self.data[idx011  ]as usize * (red_b*gre_a*blu_a) + 
-                    self.data[idx100  ]as usize * (red_a*gre_b*blu_b) +
-                    self.data[idx101  ]as usize * (red_a*gre_b*blu_a) +
-                    self.data[idx110  ]as usize * (red_a*gre_a*blu_b) +
-                    self.data[idx111  ]as usize * (red_a*gre_a*blu_a) + div/2) / div) as u8;
-        pix[1] = ( (self.data[idx000+1]as usize * (red_b*gre_b*blu_b) +
-                    self.data[idx001+1]as usize * (red_b*gre_b*blu_a) +
-                    self.data[idx010+1]as usize * (red_b*gre_a*blu_b) +
-                    self.data[idx011+1]as usize * (red_b*gre_a*blu_a) +
-                    self.data[idx100+1]as usize * (red_a*gre_b*blu_b) +
-                    self.data[idx101+1]as usize * (red_a*gre_b*blu_a) +
-                    self.data[idx110+1]as usize * (red_a*gre_a*blu_b) +
-                    self.data[idx111+1]as usize * (red_a*gre_a*blu_a) + div/2) / div) as u8;
-        pix[2] = ( (self.data[idx000+2]as usize * (red_b*gre_b*blu_b) +
-                    self.data[idx001+2]as usize * (red_b*gre_b*blu_a) +
-                    self.data[idx010+2]as usize * (red_b*gre_a*blu_b) +
-                    self.data[idx011+2]as usize * (red_b*gre_a*blu_a) +
-                    self.data[idx100+2]as usize * (red_a*gre_b*blu_b) +
-                    self.data[idx101+2]as usize * (red_a*gre_b*blu_a) +
-                    self.data[idx110+2]as usize * (red_a*gre_a*blu_b) +
-                    self.data[idx111+2]as usize * (red_a*gre_a*blu_a) + div/2) / div) as u8;
+    /// Csatornaérték (0..255) -> (alsó rácspont, súly 0..=256) a trilineáris interpolációhoz.
+    fn lut_steps(&self) -> [(u32, u32); 256] {
+        let cells = (self.size - 1) as u32;
+        let mut steps = [(0u32, 0u32); 256];
+        for (v, step) in steps.iter_mut().enumerate() {
+            let pos = (v as u32 * cells * 256 + 127) / 255; // 1/256 rácsegységben
+            *step = if pos >> 8 >= cells { (cells - 1, 256) } else { (pos >> 8, pos & 255) };
+        }
+        steps
+    }
+
+    /// Egy RGBA pixel színkorrekciója a LUT-ból, elágazás nélkül, egész aritmetikával.
+    #[inline(always)]
+    fn lookup(&self, steps: &[(u32, u32); 256], pix: &mut [u8]) {
+        let size = self.size as u32;
+        let (r0, wr) = steps[pix[0] as usize];
+        let (g0, wg) = steps[pix[1] as usize];
+        let (b0, wb) = steps[pix[2] as usize];
+        let sr = 4;
+        let sg = self.size * 4;
+        let sb = self.size * self.size * 4;
+        let base = (((b0 * size + g0) * size + r0) * 4) as usize;
+        let d = &self.data[base..base + sb + sg + sr + 4]; // egyetlen határellenőrzés
+        for c in 0..3 {
+            let lerp_r = |o: usize| d[o + c] as u32 * (256 - wr) + d[o + sr + c] as u32 * wr;
+            let c0 = lerp_r(0) * (256 - wg) + lerp_r(sg) * wg;
+            let c1 = lerp_r(sb) * (256 - wg) + lerp_r(sb + sg) * wg;
+            pix[c] = ((c0 * (256 - wb) + c1 * wb + (1 << 23)) >> 24) as u8;
+        }
     }
     
     //////////////////////////////
     
-    fn calculate_weights(&self, r: i32)  -> Vec<f32> {
+    /// Normált, egydimenziós Gauss súlyok (-r..=r); a 2D kernel ezek szorzata.
+    fn calculate_weights(&self, r: usize) -> Vec<f32> {
         let sigma = (self.sharpen_radius * 0.5f32).max(0.5f32);
         let s2 = 2.0 * sigma * sigma;
-        let d = (2 * r + 1) as usize; // A teljes kernel átmérője
-        let mut weights = vec![0.0f32; d * d];
-        for dy in 0..=r {
-            for dx in 0..=r {
-                // Eltoljuk az indexet 0..2r tartományba a tároláshoz
-                let mut iy = (dy + r) as usize;
-                let mut ix = (dx + r) as usize;
-                let weight = (-((dx * dx + dy * dy) as f32) / s2).exp();
-                weights[iy * d + ix] = weight;
-                weights[ix * d + iy] = weight;
-                iy = (r - dy) as usize;
-                ix = (r - dx) as usize;
-                weights[iy * d + ix] = weight;
-                weights[ix * d + iy] = weight;
-            }
-        }
+        let mut weights: Vec<f32> = (0..=2 * r)
+            .map(|i| {
+                let d = i as f32 - r as f32;
+                (-(d * d) / s2).exp()
+            })
+            .collect();
+        let sum: f32 = weights.iter().sum();
+        weights.iter_mut().for_each(|w| *w /= sum);
         weights
     }
     
     //////////////////////////////
     
-    fn blur_pixel(
-        &self, 
-        cx: u32, 
-        cy: u32, 
-        pix: &mut image::Rgba<u8>, 
-        source_img: &image::RgbaImage, 
-        weights: &Vec<f32>, 
-        r: i32
-    ) {
-        let mut sum = [0.0f32; 3];
-        let mut total_w = 0.0f32;
-        let (width, height) = source_img.dimensions();
-        let d = (2 * r + 1) as usize; // A teljes kernel átmérője
-
-        for dy in -r..=r {
-            let iy = (dy + r) as usize;
-            let py = (cy as i32 + dy).clamp(0, height as i32 - 1) as u32;
-            for dx in -r..=r {
-                let ix = (dx + r) as usize;
-                let px = (cx as i32 + dx).clamp(0, width as i32 - 1) as u32;
-                let w = weights[iy * d + ix];
-                let p = source_img.get_pixel(px, py);
-                sum[0] += (p[0] as f32) * w;
-                sum[1] += (p[1] as f32) * w;
-                sum[2] += (p[2] as f32) * w;
-                total_w += w;
+    /// Egy sorsáv élesítése (szétválasztott Gauss: vízszintes, majd függőleges menet) és LUT.
+    /// `src` az eredeti kép, `out` a sáv sorai (y0-tól), amelyek még az eredeti értékeket tartalmazzák.
+    fn sharpen_band(&self, src: &[u8], (width, height): (usize, usize), y0: usize, out: &mut [u8],
+            weights: &[f32], steps: &[(u32, u32); 256]) {
+        let r = weights.len() / 2;
+        let rows = out.len() / (width * 4);
+        let ya = y0.saturating_sub(r);
+        let yb = (y0 + rows + r).min(height);
+
+        // vízszintes menet a sáv soraira (± r), RGB f32
+        let mut horiz = vec![0f32; (yb - ya) * width * 3];
+        for y in ya..yb {
+            let src_row = &src[y * width * 4..(y + 1) * width * 4];
+            let dst_row = &mut horiz[(y - ya) * width * 3..(y - ya + 1) * width * 3];
+            for x in 0..width {
+                let mut acc = [0f32; 3];
+                for (k, w) in weights.iter().enumerate() {
+                    let sx = (x + k).saturating_sub(r).min(width - 1);
+                    let p = &src_row[sx * 4..sx * 4 + 3];
+                    acc[0] += p[0] as f32 * w;
+                    acc[1] += p[1] as f32 * w;
+                    acc[2] += p[2] as f32 * w;
+                }
+                dst_row[x * 3..x * 3 + 3].copy_from_slice(&acc);
             }
         }
 
-        if total_w > 0.0 {
-            let center = source_img.get_pixel(cx, cy);
-            for i in 0..3 {
-                let avg = sum[i] / total_w;
-                let detail = (center[i] as f32) - avg;
-                let val = (center[i] as f32 + detail * self.sharpen_amount).clamp(0.0, 255.0);
-                pix[i] = val as u8;
+        // függőleges menet soronként, utána élesítés és LUT
+        let mut avg = vec![0f32; width * 3];
+        for (row, out_row) in out.chunks_exact_mut(width * 4).enumerate() {
+            let y = y0 + row;
+            avg.fill(0.0);
+            for (k, w) in weights.iter().enumerate() {
+                let sy = (y + k).saturating_sub(r).min(height - 1) - ya;
+                let h_row = &horiz[sy * width * 3..(sy + 1) * width * 3];
+                for (a, h) in avg.iter_mut().zip(h_row) {
+                    *a += h * w;
+                }
+            }
+            for (pix, a) in out_row.chunks_exact_mut(4).zip(avg.chunks_exact(3)) {
+                for c in 0..3 {
+                    let center = pix[c] as f32;
+                    pix[c] = (center + (center - a[c]) * self.sharpen_amount).clamp(0.0, 255.0) as u8;
+                }
+                self.lookup(steps, pix);
             }
         }
     }
     
+    /// Élesítés és LUT `band_rows` magas, párhuzamosan feldolgozott sávokban.
+    fn sharpen_in_bands(&self, img: &mut [u8], (width, height): (usize, usize), r: usize, band_rows: usize) {
+        let steps = self.lut_steps();
+        let weights = self.calculate_weights(r);
+        let source = img.to_vec(); // Olvasható másolat a szomszédokhoz
+        img[..width * height * 4].par_chunks_mut(width * 4 * band_rows).enumerate().for_each(|(band, out)| {
+            self.sharpen_band(&source, (width, height), band * band_rows, out, &weights, &steps);
+        });
+    }
+
     //////////////////////////////
     
     pub fn apply_lut(&self, img: &mut image::RgbaImage) {
+        let (width, height) = img.dimensions();
+        self.apply_lut_raw(img, width, height);
+    }
+
+    /// Ugyanaz nyers RGBA bájtokon (pl. a megosztott Slint pufferen).
+    pub fn apply_lut_raw(&self, img: &mut [u8], width: u32, height: u32) {
+        let (width, height) = (width as usize, height as usize);
+        if width == 0 || height == 0 || img.len() < width * height * 4 {
+            return;
+        }
         let r = (self.sharpen_radius*3.0+1.0) as i32 + 1;
         if r > 0 && self.sharpen_radius >= 0.2 && self.sharpen_amount != 0.0 {
-            let weights = self.calculate_weights(r);
-            let source_img = img.clone(); // Olvasható másolat a szomszédokhoz
-            img.enumerate_pixels_mut().par_bridge().for_each(|(x, y, pixel)| {
-                self.blur_pixel(x, y, pixel, &source_img, &weights, r);
-                self.apply_lut_pixel(x, y, pixel);
-            });
+            let band_rows = LUT_BAND_ROWS.max(SHARPEN_BAND_PER_RADIUS * r as usize);
+            self.sharpen_in_bands(img, (width, height), r as usize, band_rows);
         } else {
             // Ha nincs blur, csak a színkorrekció fut
-            img.enumerate_pixels_mut().for_each(|(x, y, pixel)| {
-                self.apply_lut_pixel(x, y, pixel);
+            let steps = self.lut_steps();
+            img.par_chunks_mut(width * 4 * LUT_BAND_ROWS).for_each(|out| {
+                for pix in out.chunks_exact_mut(4) {
+                    self.lookup(&steps, pix);
+                }
             });
         }
     }
@@ -430,3 +440,96 @@ impl Lut4ColorSettings {
 
 ///////////////////////////////////////////////////////////////////////////
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lebegőpontos trilineáris interpoláció a táblából, a `lookup` ellenőrzéséhez.
+    fn reference(lut: &Lut4ColorSettings, pix: [u8; 3]) -> [f64; 3] {
+        let cells = (lut.size - 1) as f64;
+        let axis = |v: u8| {
+            let pos = v as f64 * cells / 255.0;
+            let i = (pos.floor() as usize).min(lut.size - 2);
+            (i, pos - i as f64)
+        };
+        let (r, wr) = axis(pix[0]);
+        let (g, wg) = axis(pix[1]);
+        let (b, wb) = axis(pix[2]);
+        let at = |r: usize, g: usize, b: usize, c: usize| lut.data[((b * lut.size + g) * lut.size + r) * 4 + c] as f64;
+        let mut out = [0.0; 3];
+        for (c, value) in out.iter_mut().enumerate() {
+            let lerp_r = |g: usize, b: usize| at(r, g, b, c) * (1.0 - wr) + at(r + 1, g, b, c) * wr;
+            let c0 = lerp_r(g, b) * (1.0 - wg) + lerp_r(g + 1, b) * wg;
+            let c1 = lerp_r(g, b + 1) * (1.0 - wg) + lerp_r(g + 1, b + 1) * wg;
+            *value = c0 * (1.0 - wb) + c1 * wb;
+        }
+        out
+    }
+
+    #[test]
+    fn lut_steps_cover_the_grid() {
+        let lut = Lut4ColorSettings::new();
+        let steps = lut.lut_steps();
+        assert_eq!(steps[0], (0, 0));
+        assert_eq!(steps[255], (lut.size as u32 - 2, 256));
+        let mut last = 0;
+        for (v, (cell, weight)) in steps.iter().enumerate() {
+            let pos = cell * 256 + weight;
+            assert!(pos >= last, "not monotonic at {}", v);
+            assert!(*weight <= 256 && (*cell as usize) < lut.size - 1);
+            last = pos;
+        }
+    }
+
+    #[test]
+    fn lookup_with_identity_lut_keeps_the_pixel() {
+        let lut = Lut4ColorSettings::new();
+        let steps = lut.lut_steps();
+        for v in 0..=255u8 {
+            for (r, g, b) in [(v, v, v), (v, 255 - v, v / 2), (255 - v, v / 3, v)] {
+                let mut pix = [r, g, b, 77];
+                lut.lookup(&steps, &mut pix);
+                for (out, inp) in pix[..3].iter().zip([r, g, b]) {
+                    assert!(out.abs_diff(inp) <= 1, "{:?} -> {:?}", (r, g, b), pix);
+                }
+                assert_eq!(pix[3], 77, "alpha must stay");
+            }
+        }
+    }
+
+    #[test]
+    fn lookup_matches_float_trilinear_interpolation() {
+        let mut lut = Lut4ColorSettings::new();
+        for (i, value) in lut.data.iter_mut().enumerate() {
+            *value = ((i * 37 + (i / 4) * (i / 4) % 251) % 256) as u8;
+        }
+        let steps = lut.lut_steps();
+        let mut seed = 12345u32;
+        for _ in 0..20000 {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let [r, g, b, _] = seed.to_le_bytes();
+            let mut pix = [r, g, b, 255];
+            lut.lookup(&steps, &mut pix);
+            let expected = reference(&lut, [r, g, b]);
+            for c in 0..3 {
+                assert!((pix[c] as f64 - expected[c]).abs() <= 1.0,
+                    "{:?} channel {}: {} vs {:.2}", (r, g, b), c, pix[c], expected[c]);
+            }
+        }
+    }
+
+    #[test]
+    fn sharpen_does_not_depend_on_the_band_height() {
+        let mut lut = Lut4ColorSettings::default();
+        let colset = ColorSettings { sharpen_radius: 1.5, sharpen_amount: 0.8, ..ColorSettings::default() };
+        lut.update_lut(&colset);
+        let (width, height) = (13, 70);
+        let img: Vec<u8> = (0..width * height * 4).map(|i| (i * 37 % 251) as u8).collect();
+        let mut banded = img.clone();
+        lut.sharpen_in_bands(&mut banded, (width, height), 6, 9);
+        let mut whole = img;
+        lut.sharpen_in_bands(&mut whole, (width, height), 6, height);
+        assert_eq!(banded, whole);
+    }
+}
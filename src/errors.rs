@@ -0,0 +1,54 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+// Egységes hiba a betöltés, mentés és vágólap műveletekhez:
+// melyik művelet, melyik fájl, és mi volt az ok. A UI értesítésben ez a szöveg jelenik meg.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorAction {
+    Load,
+    Save,
+    Clipboard,
+}
+
+#[derive(Debug, Clone)]
+pub struct ViewerError {
+    pub action: ErrorAction,
+    pub path: Option<PathBuf>,
+    pub cause: String,
+}
+
+pub type ViewerResult<T> = Result<T, ViewerError>;
+
+impl ViewerError {
+    pub fn load(path: &Path, cause: impl fmt::Display) -> Self {
+        Self { action: ErrorAction::Load, path: Some(path.to_path_buf()), cause: cause.to_string() }
+    }
+
+    pub fn save(path: &Path, cause: impl fmt::Display) -> Self {
+        Self { action: ErrorAction::Save, path: Some(path.to_path_buf()), cause: cause.to_string() }
+    }
+
+    pub fn clipboard(cause: impl fmt::Display) -> Self {
+        Self { action: ErrorAction::Clipboard, path: None, cause: cause.to_string() }
+    }
+
+    pub fn file_name(&self) -> String {
+        self.path.as_ref()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Display for ViewerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.action {
+            ErrorAction::Load => write!(f, "Cannot open {}\n{}", self.file_name(), self.cause),
+            ErrorAction::Save => write!(f, "Cannot save {}\n{}", self.file_name(), self.cause),
+            ErrorAction::Clipboard => write!(f, "Clipboard error\n{}", self.cause),
+        }
+    }
+}
+
+impl std::error::Error for ViewerError {}
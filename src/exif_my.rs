@@ -686,46 +686,61 @@ impl ExifBlock {
         }
     }
     
+    // Minden olvasás határ-ellenőrzött: a pufferen kívüli cím 0-t ad, a hibás fájl nem okoz pánikot.
+    fn bytes_at<const N: usize>(buff: &[u8], pos: usize) -> [u8; N] {
+        pos.checked_add(N)
+            .and_then(|end| buff.get(pos..end))
+            .and_then(|b| b.try_into().ok())
+            .unwrap_or([0u8; N])
+    }
+
     fn read_buff_u16(&self, buff :&[u8], pos: usize) -> u16 {
-        let bytes = buff[pos..pos + 2].try_into().unwrap();
+        let bytes = Self::bytes_at(buff, pos);
         if self.motorola_order { u16::from_be_bytes(bytes) }
         else { u16::from_le_bytes(bytes) }
     }
     
     fn read_u16(&self, pos: usize) -> u16 {
-        let bytes = self.raw_exif[pos..pos + 2].try_into().unwrap();
-        if self.motorola_order { u16::from_be_bytes(bytes) }
-        else { u16::from_le_bytes(bytes) }
+        self.read_buff_u16(&self.raw_exif, pos)
     }
     
     fn read_u32(&self, pos: usize) -> u32 {
-        let bytes = self.raw_exif[pos..pos + 4].try_into().unwrap();
+        let bytes = Self::bytes_at(&self.raw_exif, pos);
         if self.motorola_order { u32::from_be_bytes(bytes) }
         else { u32::from_le_bytes(bytes) }
     }
     
     fn read_i32(&self, pos: usize) -> i32 {
-        let bytes = self.raw_exif[pos..pos + 4].try_into().unwrap();
-        if self.motorola_order { i32::from_be_bytes(bytes) }
-        else { i32::from_le_bytes(bytes) }
+        self.read_u32(pos) as i32
     }
     
     fn read_f32(&self, pos: usize) -> f32 {
-        let bytes = self.raw_exif[pos..pos + 4].try_into().unwrap();
-        if self.motorola_order { f32::from_be_bytes(bytes) }
-        else { f32::from_le_bytes(bytes) }
+        f32::from_bits(self.read_u32(pos))
     }
     
     fn read_f64(&self, pos: usize) -> f64 {
-        let bytes = self.raw_exif[pos..pos + 4].try_into().unwrap();
+        let bytes = Self::bytes_at(&self.raw_exif, pos);
         if self.motorola_order { f64::from_be_bytes(bytes) }
         else { f64::from_le_bytes(bytes) }
     }
 
+    /// A raw_exif egy szakasza, vagy None, ha kilóg a pufferből.
+    fn raw_slice(&self, pos: usize, len: usize) -> Option<&[u8]> {
+        self.raw_exif.get(pos..pos.checked_add(len)?)
+    }
+
+    /// Mentés előtti javítás a raw_exif-ben; pufferen kívüli címre nem ír.
+    fn patch_bytes(&mut self, pos: usize, bytes: &[u8]) {
+        if let Some(end) = pos.checked_add(bytes.len()) && let Some(target) = self.raw_exif.get_mut(pos..end) {
+            target.copy_from_slice(bytes);
+        }
+    }
+
     fn convert_format_usize(&self, valueptr: usize, format:& FMT) -> usize {
+       let byte = self.raw_exif.get(valueptr).copied().unwrap_or(0);
        match format {
-            FMT::BYTE   => (self.raw_exif[valueptr] as u8) as usize,
-            FMT::SBYTE  => (self.raw_exif[valueptr] as i8) as usize,
+            FMT::BYTE   => byte as usize,
+            FMT::SBYTE  => (byte as i8) as usize,
             FMT::USHORT => (self.read_u16(valueptr)) as usize,
             FMT::SSHORT => (self.read_u16(valueptr) as i16) as usize,
             FMT::ULONG  => (self.read_u32(valueptr)) as usize,
@@ -748,61 +763,65 @@ impl ExifBlock {
             let (nxf,nx) = if ((xres+0.5) as u32) as f32 == xres { (1.0,1u32) } else { (100000.0,100000u32) };
             let dx = (xres * nxf + 0.5) as u32;
             let mut bytes = if self.motorola_order { dx.to_be_bytes() } else { dx.to_le_bytes() };
-            self.raw_exif[entry.offset..entry.offset+4].copy_from_slice(&bytes);
+            self.patch_bytes(entry.offset, &bytes);
             bytes = if self.motorola_order { nx.to_be_bytes() } else { nx.to_le_bytes() };
-            self.raw_exif[entry.offset+4..entry.offset+8].copy_from_slice(&bytes);
+            self.patch_bytes(entry.offset+4, &bytes);
         }
 
         if let Some(entry) = self.find_tag("YResolution",0,true) {
             let (nyf,ny) = if ((yres+0.5) as u32) as f32 == yres { (1.0,1u32) } else { (100000.0,100000u32) };
             let dy = (yres * nyf + 0.5) as u32;
             let mut bytes = if self.motorola_order { dy.to_be_bytes() } else { dy.to_le_bytes() };
-            self.raw_exif[entry.offset..entry.offset+4].copy_from_slice(&bytes);
+            self.patch_bytes(entry.offset, &bytes);
             bytes = if self.motorola_order { ny.to_be_bytes() } else { ny.to_le_bytes() };
-            self.raw_exif[entry.offset+4..entry.offset+8].copy_from_slice(&bytes);
+            self.patch_bytes(entry.offset+4, &bytes);
         }
 
         if let Some(entry) = self.find_tag("Orientation",0,true) {
             let ori = 1u16;
             let bytes = if self.motorola_order { ori.to_be_bytes() } else { ori.to_le_bytes() };
-            self.raw_exif[entry.offset..entry.offset+2].copy_from_slice(&bytes);
+            self.patch_bytes(entry.offset, &bytes);
         }
 
         if let Some(entry) = self.find_tag("DateTime",0,true) {
             let current_date = chrono::Local::now().format("%Y:%m:%d %H:%M:%S").to_string();
             let bytes = current_date.as_bytes();
             if bytes.len() <= 20 {
-                self.raw_exif[entry.offset..entry.offset + bytes.len()].copy_from_slice(bytes);
+                self.patch_bytes(entry.offset, bytes);
             }
         }
 
         if let Some(entry) = self.find_tag("PixelXDimension",0,true) {
             let bytes = if self.motorola_order { w.to_be_bytes() } else { w.to_le_bytes() };
-            self.raw_exif[entry.offset..entry.offset+4].copy_from_slice(&bytes);
+            self.patch_bytes(entry.offset, &bytes);
         }
         else if let Some(entry) = self.find_tag("ImageWidth",0,true) {
             let bytes = if self.motorola_order { w.to_be_bytes() } else { w.to_le_bytes() };
-            self.raw_exif[entry.offset..entry.offset+4].copy_from_slice(&bytes);
+            self.patch_bytes(entry.offset, &bytes);
         }
 
         if let Some(entry) = self.find_tag("PixelYDimension",0,true) {
             let bytes = if self.motorola_order { h.to_be_bytes() } else { h.to_le_bytes() };
-            self.raw_exif[entry.offset..entry.offset+4].copy_from_slice(&bytes);
+            self.patch_bytes(entry.offset, &bytes);
         }
         else if let Some(entry) = self.find_tag("ImageLength",0,true) {
             let bytes = if self.motorola_order { h.to_be_bytes() } else { h.to_le_bytes() };
-            self.raw_exif[entry.offset..entry.offset+4].copy_from_slice(&bytes);
+            self.patch_bytes(entry.offset, &bytes);
         }
 
         if let Some(entry) = self.find_tag("Orientation",0,true) {
             let ori = 1u16;
             let bytes = if self.motorola_order { ori.to_be_bytes() } else { ori.to_le_bytes() };
-            self.raw_exif[entry.offset..entry.offset+2].copy_from_slice(&bytes);
+            self.patch_bytes(entry.offset, &bytes);
         }
     }
 
     pub fn open(&mut self, exifsection: &[u8],  length: usize) -> Result<ExifBlock, String> {
         let exifheader: [u8; 6] = [b'E',b'x',b'i',b'f',0,0];
+        if exifsection.len() < 14 {
+            return Err("Corrupt exif header: too short".into());
+        }
+        let length = length.min(exifsection.len());
         if exifsection[0..6] != exifheader {
             return Err("No exif header".into());
         }
@@ -820,7 +839,14 @@ impl ExifBlock {
             return Err("Corrupt exif header: Invalid Exif start (1)".into())
         }
 
-        let firstoffset = self.read_buff_u16(&exifsection,10) as usize;
+        // TIFF fejléc: 2 bájt bájtsorrend ("II" vagy "MM"), 2 bájt 42-es azonosító, majd
+        // 4 bájton az első IFD eltolása a fejléc elejétől, a fenti bájtsorrendben.
+        let offset_bytes = Self::bytes_at::<4>(exifsection, 10);
+        let firstoffset = if self.motorola_order {
+            u32::from_be_bytes(offset_bytes)
+        } else {
+            u32::from_le_bytes(offset_bytes)
+        } as usize;
         if firstoffset < 8 || firstoffset > 32000 {
             return Err("Corrupt exif header: Suspicious offset of first IFD value".into());
         }
@@ -836,8 +862,7 @@ impl ExifBlock {
         self.nesting_level-=1;
         
         if self.thumbnailsize != 0 && self.thumbnailoffset != 0 {
-            if self.thumbnailsize + self.thumbnailoffset <= length {
-                let raw_bytes = &self.raw_exif[self.thumbnailoffset..self.thumbnailoffset + self.thumbnailsize];
+            if let Some(raw_bytes) = self.raw_slice(self.thumbnailoffset, self.thumbnailsize) {
                 let value = json!(general_purpose::STANDARD.encode(raw_bytes));
                 json.insert("Thumbnail".to_string(), value);
             }
@@ -851,6 +876,80 @@ impl ExifBlock {
     }
 
 
+    /// TIFF alapú RAW fájl (CR2, NEF, ARW, DNG) a beágyazott előnézetek kereséséhez.
+    /// A data a teljes fájl "Exif\0\0" fejléccel; a könyvtárakat nem dolgozza fel.
+    pub fn open_container(&mut self, data: Vec<u8>) -> Result<(), String> {
+        if data.len() < 14 || data[0..6] != *b"Exif\0\0" {
+            return Err("No TIFF header".into());
+        }
+        match &data[6..8] {
+            b"MM" => self.motorola_order = true,
+            b"II" => self.motorola_order = false,
+            _ => return Err("Invalid TIFF byte order".into()),
+        }
+        self.raw_exif_length = data.len();
+        self.raw_exif = data;
+        if self.read_u16(8) != 0x2a {
+            return Err("Invalid TIFF start".into());
+        }
+        Ok(())
+    }
+
+    /// A beágyazott JPEG-ek a legnagyobbal kezdve: az IFD0 lánc, a SubIFD-k és az EXIF
+    /// könyvtár JPEGInterchangeFormat mezői, valamint a JPEG tömörítésű egycsíkos képek.
+    pub fn embedded_jpegs(&self) -> Vec<&[u8]> {
+        let mut found: Vec<(usize, usize)> = Vec::new();
+        let mut dirs = vec![self.read_u32(10) as usize];
+        let mut seen: Vec<usize> = Vec::new();
+        while let Some(dir) = dirs.pop() {
+            if dir < 8 || seen.contains(&dir) || seen.len() >= 64 {
+                continue;
+            }
+            seen.push(dir);
+            let start = dir + 6;
+            let entries = self.read_u16(start) as usize;
+            if entries == 0 || Self::dir_entry_addr(start, entries) > self.raw_exif.len() {
+                continue;
+            }
+            let (mut jpeg, mut strip) = ((0, 0), (0, 0));
+            let mut compression = 0;
+            for idx in 0..entries {
+                let entry = Self::dir_entry_addr(start, idx);
+                let format = FMT::from(self.read_u16(entry + 2));
+                let count = self.read_u32(entry + 4) as usize;
+                let value = self.convert_format_usize(entry + 8, &format);
+                match self.read_u16(entry) {
+                    0x0103 => compression = value,
+                    0x0111 if count == 1 => strip.0 = value,
+                    0x0117 if count == 1 => strip.1 = value,
+                    0x0201 => jpeg.0 = value,
+                    0x0202 => jpeg.1 = value,
+                    0x8769 => dirs.push(self.read_u32(entry + 8) as usize),
+                    0x014A => {
+                        // egy SubIFD-nél az érték maga az eltolás, többnél egy tömbre mutat
+                        let list = if count == 1 { entry + 8 } else { self.read_u32(entry + 8) as usize + 6 };
+                        for k in 0..count.min(16) {
+                            dirs.push(self.read_u32(list + 4 * k) as usize);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            dirs.push(self.read_u32(Self::dir_entry_addr(start, entries)) as usize);
+            found.push(jpeg);
+            if compression == 6 || compression == 7 {
+                found.push(strip);
+            }
+        }
+        found.sort_by_key(|&(_, len)| std::cmp::Reverse(len));
+        found.dedup();
+        found.into_iter()
+            .filter(|&(_, len)| len > 0)
+            .filter_map(|(pos, len)| self.raw_slice(pos + 6, len))
+            .filter(|data| data.starts_with(&[0xFF, 0xD8]))
+            .collect()
+    }
+
     fn dir_entry_addr(start: usize, entry: usize) -> usize {
         start + 2 + 12 * entry
     }
@@ -888,7 +987,7 @@ impl ExifBlock {
                 return Err(format!("Corrupt exif header: Illegal number format {:?} for tag {:?}", format, tag.name));
             }
             let components = self.read_u32(direntry+4) as usize;
-            let bytecount = components * BYTESPERFORMAT[format.clone() as usize];
+            let bytecount = components.saturating_mul(BYTESPERFORMAT[format.clone() as usize]);
             
             let mut json_tag: Map<String, Value> = Map::new();
             json_tag.insert("type".to_string(),json!(format));
@@ -905,6 +1004,9 @@ impl ExifBlock {
                 // 4 bytes or less and value is in the dir entry itself
                 direntry+8
             };
+            if self.raw_slice(valueptr, bytecount).is_none() {
+                return Err(format!("Corrupt exif header: value of tag {:?} is outside the exif block", tag.name));
+            }
 
             if self.lastexifrefd < valueptr+bytecount {
                 // keep track of last byte in the exif header that was actually referenced.
@@ -1017,7 +1119,7 @@ impl ExifBlock {
             if components < 1 || components > 32768 {
                 return Err(format!("Corrupt exif header: bad component number"));
             }
-            let bytecount = components * BYTESPERFORMAT[format.clone() as usize];
+            let bytecount = components.saturating_mul(BYTESPERFORMAT[format.clone() as usize]);
 
             let mut json_tag: Map<String, Value> = Map::new();
             json_tag.insert("type".to_string(),json!(format));
@@ -1034,6 +1136,9 @@ impl ExifBlock {
                 // 4 bytes or less and value is in the dir entry itself
                 direntry+8
             };
+            if self.raw_slice(valueptr, bytecount).is_none() {
+                return Err(format!("Corrupt exif header: value of tag {:?} is outside the exif block", tag.name));
+            }
 
             if self.lastexifrefd < valueptr+bytecount {
                 // keep track of last byte in the exif header that was actually referenced.
@@ -1066,6 +1171,9 @@ impl ExifBlock {
 
     fn get_entry_value(&mut self, format: FMT, mut valueptr: usize, components: usize,
             bytecount: usize, is_make: bool, _is_note: bool) -> ( Value, bool) {
+        if self.raw_slice(valueptr, bytecount).is_none() {
+            return (json!(null), false);
+        }
         match format {
             FMT::UNDEFINED | FMT::STRING | FMT::UTF_8 => {
                 let raw_bytes = &self.raw_exif[valueptr..valueptr + bytecount];
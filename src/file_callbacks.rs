@@ -3,8 +3,13 @@ use crate::ColorCorrectionWindow;
 use crate::AboutWindow;
 use crate::InfoWindow;
 use crate::SaveWindow;
+use crate::TimelineWindow;
 use crate::image_processing::*;
 use crate::colors::*;
+use crate::memory::format_mb;
+use crate::file_handlers::format_mismatch;
+use crate::anim_edit::FrameEdit;
+use crate::exif_my::ExifBlock;
 use crate::Pf32;
 
 use slint::{ComponentHandle,Image,Color,SharedPixelBuffer,Rgba8Pixel};
@@ -23,6 +28,7 @@ pub fn file_callbacks(
         about_ui: AboutWindow,
         info_ui: InfoWindow,
         save_window_ui: SaveWindow,
+        timeline_ui: TimelineWindow,
         state: Rc<RefCell<ImageViewer>>)
 {
     let ui = ui_weak.unwrap();    
@@ -39,7 +45,7 @@ pub fn file_callbacks(
             (Some(PathBuf::from(&args[1])), false)
         } else {
             // 2. Ha nincs, megnézzük a vágólapot (Ctrl+C-vel másolt kép)
-            (save_clipboard_image(), true)
+            (save_clipboard_image().ok(), true)
         };
         
         let mut viewer = state_copy.borrow_mut();
@@ -105,6 +111,29 @@ pub fn file_callbacks(
         viewer.about_window = Some(about_ui);
         viewer.info_window = Some(info_ui);
         viewer.save_window = Some(save_window_ui);
+        viewer.timeline_window = Some(timeline_ui);
+        viewer.sync_onion_controls();
+    }
+
+    {
+        // a valódi textúra méret csak a renderer felállásakor kérdezhető le
+        let value = state_copy.clone();
+        let notifier = ui.window().set_rendering_notifier(move |rendering_state, graphics_api| {
+            if let slint::RenderingState::RenderingSetup = rendering_state {
+                let size = match graphics_api {
+                    slint::GraphicsAPI::NativeOpenGL { get_proc_address } => query_gl_max_texture_size(get_proc_address),
+                    _ => None,
+                };
+                // a renderelés közben a viewer foglalt lehet, ezért később állítjuk be
+                let value = value.clone();
+                slint::Timer::single_shot(std::time::Duration::ZERO, move || {
+                    value.borrow_mut().set_max_texture_size(size);
+                });
+            }
+        });
+        if notifier.is_err() { // szoftveres renderer: nincs GL környezet
+            state_copy.borrow_mut().set_max_texture_size(None);
+        }
     }
 
     {
@@ -116,20 +145,8 @@ pub fn file_callbacks(
                 move || {            
                     if let Some(s_ui) = settings_handle.upgrade() { 
                         let mut viewer = state_rc.borrow_mut();
-                        let colset = s_ui.get_colset();
-                        viewer.color_settings.show_r = colset.show_r;
-                        viewer.color_settings.show_g = colset.show_g;
-                        viewer.color_settings.show_b = colset.show_b;
-                        viewer.color_settings.invert = colset.invert;
-                        viewer.color_settings.rotate = Rotate::from_u8(colset.rotate as u8);
-                        viewer.color_settings.oklab  = colset.oklab;
-                        viewer.color_settings.gamma = s_ui.get_gamma();
-                        viewer.color_settings.contrast = s_ui.get_contrast();
-                        viewer.color_settings.brightness = s_ui.get_brightness();
-                        viewer.color_settings.hue_shift = s_ui.get_hue_shift();
-                        viewer.color_settings.saturation = s_ui.get_saturation();
-                        viewer.color_settings.sharpen_amount = s_ui.get_sharpen_amount();
-                        viewer.color_settings.sharpen_radius = s_ui.get_sharpen_radius();
+                        viewer.interactive_timer.stop();
+                        read_color_settings(&s_ui, &mut viewer.color_settings);
                         viewer.review(true, false);
                     }
                 }
@@ -137,6 +154,33 @@ pub fn file_callbacks(
         }
     }
 
+    {
+        // csúszka húzása közben csak a látható rész frissül; ha a húzás abbamarad
+        // (pl. billentyűvel állított csúszkánál nincs released), a teljes kép is elkészül
+        let value = state_copy.clone();
+        if let Some(settings_ui)  = &state_copy.borrow().settings_window {
+            let settings_handle = settings_ui.as_weak();
+            settings_ui.on_dragging({
+                let state_rc = value.clone();
+                move || {            
+                    if let Some(s_ui) = settings_handle.upgrade() { 
+                        let mut viewer = state_rc.borrow_mut();
+                        read_color_settings(&s_ui, &mut viewer.color_settings);
+                        viewer.review_interactive();
+                        let state_final = state_rc.clone();
+                        viewer.interactive_timer.start(
+                            slint::TimerMode::SingleShot,
+                            std::time::Duration::from_millis(400),
+                            move || {
+                                state_final.borrow_mut().review(true, false);
+                            },
+                        );
+                    }
+                }
+            });
+        }
+    }
+
     {
         let value = state_copy.clone();
         if let Some(settings_ui)  = &state_copy.borrow().settings_window {
@@ -171,6 +215,109 @@ pub fn file_callbacks(
         }
     }
 
+    {
+        let value = state_copy.clone();
+        if let Some(t_ui)  = &state_copy.borrow().timeline_window {
+            let state_rc = value.clone();
+            t_ui.on_seek(move |frame| {
+                state_rc.borrow_mut().seek_frame(frame);
+            });
+            let state_rc = value.clone();
+            t_ui.on_play(move || {
+                let mut viewer = state_rc.borrow_mut();
+                on_play_animation(&mut viewer);
+                viewer.sync_timeline();
+            });
+            let state_rc = value.clone();
+            t_ui.on_speed_changed(move |speed| {
+                state_rc.borrow_mut().anim_speed = speed.clamp(0.1, 4.0);
+            });
+            let state_rc = value.clone();
+            t_ui.on_ping_pong_changed(move |on| {
+                let mut viewer = state_rc.borrow_mut();
+                viewer.anim_ping_pong = on;
+                viewer.anim_reverse = false;
+            });
+            let state_rc = value.clone();
+            t_ui.on_export_frame(move || {
+                state_rc.borrow_mut().export_current_frame();
+            });
+            let state_rc = value.clone();
+            t_ui.on_export_all(move || {
+                state_rc.borrow_mut().export_all_frames();
+            });
+            let state_rc = value.clone();
+            let t_handle = t_ui.as_weak();
+            t_ui.on_edit_frames(move |name| {
+                let Some(t_ui) = t_handle.upgrade() else {
+                    return;
+                };
+                let first = (t_ui.get_sel_first() - 1).max(0) as usize;
+                let last = (t_ui.get_sel_last() - 1).max(0) as usize;
+                let edit = match name.as_str() {
+                    "delete" => FrameEdit::Delete,
+                    "duplicate" => FrameEdit::Duplicate,
+                    "move_back" => FrameEdit::MoveBack,
+                    "move_forward" => FrameEdit::MoveForward,
+                    "delay" => FrameEdit::Delay(std::time::Duration::from_millis(t_ui.get_delay_ms().max(10) as u64)),
+                    "reverse" => FrameEdit::Reverse,
+                    "crop" => FrameEdit::Crop,
+                    _ => return,
+                };
+                state_rc.borrow_mut().edit_frames(edit, first, last);
+            });
+            let state_rc = value.clone();
+            let t_handle = t_ui.as_weak();
+            t_ui.on_onion_changed(move || {
+                let Some(t_ui) = t_handle.upgrade() else {
+                    return;
+                };
+                let tint = |c: slint::Color| [c.red(), c.green(), c.blue()];
+                state_rc.borrow_mut().set_onion_skin(t_ui.get_onion(), t_ui.get_onion_opacity(),
+                    tint(t_ui.get_onion_prev()), tint(t_ui.get_onion_next()));
+            });
+            let state_rc = value.clone();
+            t_ui.on_thumbs_ready(move || {
+                state_rc.borrow_mut().receive_timeline_thumbs();
+            });
+            let state_rc = value.clone();
+            t_ui.on_hide(move || {
+                let mut viewer = state_rc.borrow_mut();
+                if viewer.show_timeline {
+                    viewer.toggle_timeline();
+                }
+            });
+        }
+    }
+
+    {
+        let value = state_copy.clone();
+        if let Some(a_ui) = &state_copy.borrow().assemble_window {
+            let state_rc = value.clone();
+            a_ui.on_select_files(move || {
+                state_rc.borrow_mut().select_assemble_files();
+            });
+            let state_rc = value.clone();
+            a_ui.on_whole_folder(move || {
+                state_rc.borrow_mut().assemble_whole_folder();
+            });
+            let state_rc = value.clone();
+            a_ui.on_assemble(move || {
+                state_rc.borrow_mut().assemble_animation();
+            });
+            let state_rc = value.clone();
+            a_ui.on_cancel(move || {
+                state_rc.borrow_mut().cancel_assemble();
+            });
+            let a_handle = a_ui.as_weak();
+            a_ui.on_hide(move || {
+                if let Some(a_ui) = a_handle.upgrade() {
+                    a_ui.hide().unwrap();
+                }
+            });
+        }
+    }
+
     {
         //let value = state_copy.clone();
         if let Some(info_ui)  = &state_copy.borrow().info_window {
@@ -218,6 +365,7 @@ pub fn file_callbacks(
                             if let Some(ref mut dial) = viewer.save_dialog {
                                 dial.quality = s_ui.get_quality() as u8;
                                 dial.lossless = s_ui.get_lossless();
+                                dial.effort = s_ui.get_effort().round().clamp(1.0, 10.0) as u8;
                                 dial.include_exif = s_ui.get_include_exif();
                             }
                             viewer.completing_save();
@@ -295,6 +443,31 @@ pub fn file_callbacks(
     ui.on_reopen_file(move || {
         on_reopen_file(&mut value.borrow_mut());
     });
+
+    let value = state_copy.clone();
+    ui.on_develop_raw(move || {
+        on_develop_raw(&mut value.borrow_mut());
+    });
+
+    let value = state_copy.clone();
+    ui.on_prev_page(move || {
+        on_prev_page(&mut value.borrow_mut());
+    });
+
+    let value = state_copy.clone();
+    ui.on_next_page(move || {
+        on_next_page(&mut value.borrow_mut());
+    });
+
+    let value = state_copy.clone();
+    ui.on_save_page(move || {
+        on_save_page(&mut value.borrow_mut());
+    });
+
+    let value = state_copy.clone();
+    ui.on_export_pages(move || {
+        on_export_pages(&mut value.borrow_mut());
+    });
     
     let value = state_copy.clone();
     ui.on_prev_image(move || {
@@ -408,6 +581,36 @@ pub fn file_callbacks(
         on_loop_animation(&mut value.borrow_mut());
     });
     
+    let value = state_copy.clone();
+    ui.on_timeline_animation(move || {
+        value.borrow_mut().toggle_timeline();
+    });
+
+    let value = state_copy.clone();
+    ui.on_onion_skin(move || {
+        value.borrow_mut().toggle_onion_skin();
+    });
+
+    let value = state_copy.clone();
+    ui.on_assemble_animation(move || {
+        value.borrow_mut().open_assemble();
+    });
+    
+    let value = state_copy.clone();
+    ui.on_image_loaded(move || {
+        value.borrow_mut().image_loaded();
+    });
+
+    let value = state_copy.clone();
+    ui.on_animation_saved(move || {
+        value.borrow_mut().animation_saved();
+    });
+
+    let value = state_copy.clone();
+    ui.on_viewport_changed(move || {
+        value.borrow_mut().update_tiles();
+    });
+
     let value = state_copy.clone();
     ui.on_mouse_move(move |posx,posy| {
         let mut viewer = value.borrow_mut();
@@ -467,10 +670,15 @@ pub fn file_callbacks(
             else {
                 if shift {
                     if text == "s" || text == "S" { on_save_view(&mut state.borrow_mut()); return true; }
+                    if text == "r" || text == "R" { on_develop_raw(&mut state.borrow_mut()); return true; }
+                    if text == "b" || text == "B" { on_prev_page(&mut state.borrow_mut()); return true; }
+                    if text == "n" || text == "N" { on_next_page(&mut state.borrow_mut()); return true; }
+                    if text == "o" || text == "O" { state.borrow_mut().toggle_onion_skin(); return true; }
                 }
                 else {
                     if text == "i" { on_info_clicked(&mut state.borrow_mut(), false); return true; }
                     if text == "c" { on_color_setting(&mut state.borrow_mut()); return true; }
+                    if text == "t" { state.borrow_mut().toggle_timeline(); return true; }
                     if text == "o" { on_open_file(&mut state.borrow_mut()); return true; }
                     if text == "r" { on_reopen_file(&mut state.borrow_mut()); return true; }
                     if text == "b" { on_prev_image(&mut state.borrow_mut()); return true; }
@@ -494,6 +702,15 @@ pub fn file_callbacks(
                     if text == left_ar { on_back_animation(&mut state.borrow_mut()); return true; }
                     if text == right_ar { on_forward_animation(&mut state.borrow_mut()); return true; }
                     if text == esc {
+                        if state.borrow_mut().cancel_page_export() {
+                            return true;
+                        }
+                        if state.borrow_mut().cancel_anim_save() {
+                            return true;
+                        }
+                        if state.borrow_mut().cancel_frame_export() {
+                            return true;
+                        }
                         if let Some(ui) = ui_weak_keys.upgrade() {
                             let _ = ui.window().hide();
                         }
@@ -587,6 +804,29 @@ fn on_reopen_file(viewer: &mut ImageViewer) {
     viewer.load_image(true);
 }
 
+fn on_develop_raw(viewer: &mut ImageViewer) {
+    println!("on_develop_raw");
+    viewer.develop_raw();
+}
+
+fn on_prev_page(viewer: &mut ImageViewer) {
+    viewer.change_page(-1);
+}
+
+fn on_next_page(viewer: &mut ImageViewer) {
+    viewer.change_page(1);
+}
+
+fn on_save_page(viewer: &mut ImageViewer) {
+    println!("on_save_page");
+    viewer.save_page();
+}
+
+fn on_export_pages(viewer: &mut ImageViewer) {
+    println!("on_export_pages");
+    viewer.export_all_pages();
+}
+
 fn on_prev_image(viewer: &mut ImageViewer) {
     //println!("on_prev_image");
     viewer.navigation(-1);
@@ -603,6 +843,9 @@ pub fn on_info_clicked(viewer: &mut ImageViewer, refresh_only: bool) {
         let info_handle = info_ui.as_weak();    
         if let Some(s_ui) = info_handle.upgrade() {
             s_ui.set_filename( viewer.image_name.clone().into());
+            let warning = viewer.image_full_path.as_ref()
+                .and_then(|path| format_mismatch(path, viewer.image_format));
+            s_ui.set_format_warning(warning.unwrap_or_default().into());
             s_ui.set_imagesize(
                 format!( "{} x {} pixel",
                     viewer.original_image_size.x,
@@ -662,6 +905,15 @@ pub fn on_info_clicked(viewer: &mut ImageViewer, refresh_only: bool) {
                 }
             }
             s_ui.set_resolution( s.into());
+            let usage = viewer.memory_usage();
+            s = match viewer.memory_budget_bytes() {
+                0 => format_mb(usage.total()),
+                budget => format!("{} / {}", format_mb(usage.total()), format_mb(budget)),
+            };
+            s_ui.set_memory( s.into());
+            let (animation, delays) = viewer.anim_data.as_ref().map(animation_text).unwrap_or_default();
+            s_ui.set_animation(animation.into());
+            s_ui.set_frame_delays(delays.into());
             if let Some(exif) = &viewer.exif {
                 s_ui.set_exif( true);
                 if let Some(f) = exif.get_field("DateTimeOriginal".into()) {
@@ -670,6 +922,8 @@ pub fn on_info_clicked(viewer: &mut ImageViewer, refresh_only: bool) {
                 if let Some(f) = exif.get_field("Model".into()) {
                     s_ui.set_model(f.into());
                 }
+                s_ui.set_lens(exif.get_field("LensModel").unwrap_or_default().into());
+                s_ui.set_exposure(exposure_text(exif).into());
                 let la = exif .get_num_field("GPSLatitude".into());
                 let lo = exif.get_num_field("GPSLongitude".into());
                 let lar = exif.get_field("GPSLatitudeRef".into());
@@ -884,24 +1138,29 @@ fn on_invert_channels(viewer: &mut ImageViewer, _val : bool, _no_set: bool) {
 
 fn on_play_animation(viewer: &mut ImageViewer) {
     //println!("on_play_animation");
-    if let Some(anim) = &viewer.anim_data {
+    if viewer.anim_data.is_some() {
         viewer.anim_playing = !viewer.anim_playing;
         if viewer.anim_playing {
-            if !viewer.anim_loop&& viewer.current_frame + 1 == anim.total_frames {
-                viewer.current_frame = 0;
+            viewer.last_frame_time = std::time::Instant::now();
+            // a lejátszások elfogytak: elölről
+            if viewer.plays_exhausted() {
+                viewer.anim_plays_done = 0;
+                viewer.anim_reverse = false;
+                viewer.show_frame(0);
+            } else {
+                viewer.review(true, false);
             }
-            viewer.review(true, false);
         }
+        viewer.update_onion_skin();
     }
 }
 
 fn on_begin_animation(viewer: &mut ImageViewer) {
     //println!("on_begin_animation");
-    if let Some(anim) = &viewer.anim_data {
+    if viewer.anim_data.is_some() {
         viewer.current_frame = 0;
         if !viewer.anim_playing {
-            viewer.original_image = Some(anim.anim_frames[viewer.current_frame].clone());
-            viewer.review(true, false);
+            viewer.show_frame(0);
         }
     }
 }
@@ -910,13 +1169,12 @@ fn on_back_animation(viewer: &mut ImageViewer) {
     //println!("on_back_animation");
     if let Some(anim) = &viewer.anim_data {
         viewer.anim_playing = false;
-        if viewer.current_frame == 0 {
-            viewer.current_frame = anim.total_frames - 1;
+        let frame = if viewer.current_frame == 0 {
+            anim.total_frames - 1
         } else {
-            viewer.current_frame -= 1;
-        }
-        viewer.original_image = Some(anim.anim_frames[viewer.current_frame].clone());
-        viewer.review(true, false);
+            viewer.current_frame - 1
+        };
+        viewer.show_frame(frame);
     }
 }
 
@@ -924,24 +1182,43 @@ fn on_forward_animation(viewer: &mut ImageViewer) {
     //println!("on_forward_animation");
     if let Some(anim) = &viewer.anim_data {
         viewer.anim_playing = false;
-        viewer.current_frame = (viewer.current_frame + 1) % anim.total_frames;
-        viewer.original_image = Some(anim.anim_frames[viewer.current_frame].clone());
-        viewer.review(true, false);
+        let frame = (viewer.current_frame + 1) % anim.total_frames;
+        viewer.show_frame(frame);
     }
 }
 
 fn on_loop_animation(viewer: &mut ImageViewer) {
     //println!("on_loop_animation");
     viewer.anim_loop = !viewer.anim_loop;
+    viewer.anim_plays_done = 0;
     viewer.current_frame = 0;
-    if let Some(anim) = &viewer.anim_data {
-        if viewer.anim_playing {
-            viewer.original_image = Some(anim.anim_frames[0].clone());
-            viewer.review(true, false);
-        }
+    if let Some(ui) = viewer.ui_handle.as_ref().and_then(|h| h.upgrade()) {
+        ui.set_loop_checked(viewer.anim_loop);
+    }
+    if viewer.anim_data.is_some() && viewer.anim_playing {
+        viewer.last_frame_time = std::time::Instant::now();
+        viewer.show_frame(0);
     }
 }
 
+/// A színkorrekciós ablak aktuális értékeinek átvétele.
+fn read_color_settings(s_ui: &ColorCorrectionWindow, color_settings: &mut ColorSettings) {
+    let colset = s_ui.get_colset();
+    color_settings.show_r = colset.show_r;
+    color_settings.show_g = colset.show_g;
+    color_settings.show_b = colset.show_b;
+    color_settings.invert = colset.invert;
+    color_settings.rotate = Rotate::from_u8(colset.rotate as u8);
+    color_settings.oklab  = colset.oklab;
+    color_settings.gamma = s_ui.get_gamma();
+    color_settings.contrast = s_ui.get_contrast();
+    color_settings.brightness = s_ui.get_brightness();
+    color_settings.hue_shift = s_ui.get_hue_shift();
+    color_settings.saturation = s_ui.get_saturation();
+    color_settings.sharpen_amount = s_ui.get_sharpen_amount();
+    color_settings.sharpen_radius = s_ui.get_sharpen_radius();
+}
+
 fn on_color_setting(viewer: &mut ImageViewer) {
     println!("on_color_setting");
     if let Some(settings_ui)  = &viewer.settings_window {
@@ -977,24 +1254,61 @@ fn on_color_setting(viewer: &mut ImageViewer) {
 }
 
 pub fn set_animation(viewer: &mut ImageViewer){
+    viewer.sync_timeline();
     if viewer.anim_playing {
         if let Some(anim) = &viewer.anim_data {
-            let delay = anim.delays[viewer.current_frame];
-            let total_frames = anim.total_frames;
+            // a lejátszási sebesség (0.1x - 4x) a késleltetést osztja
+            let delay = anim.delays[viewer.current_frame].div_f32(viewer.anim_speed.clamp(0.1, 4.0));
+            // a következő kocka a mostani esedékességéhez képest jön, így a review() ideje
+            // nem adódik hozzá; egy teljes kockánál nagyobb lemaradást nem pótol be
+            let now = std::time::Instant::now();
+            let mut due = viewer.last_frame_time + delay;
+            if due + delay < now {
+                due = now;
+            }
             if let Some(state_rc) = &viewer.self_weak.clone() {
                 let state_clone = state_rc.clone();
                 viewer.anim_timer.start(
                     slint::TimerMode::SingleShot,
-                    delay,
+                    due.saturating_duration_since(now),
                     move || {
                         let mut v = state_clone.borrow_mut();
-                        v.current_frame = (v.current_frame + 1) % total_frames;
-                        if let Some(frame) = v.anim_data.as_ref().and_then(|a| a.anim_frames.get(v.current_frame)) {
-                            v.original_image = Some(frame.clone());
-                            v.review(true, false);
-                        }
+                        v.last_frame_time = due;
+                        v.advance_animation();
                 });
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// Az animáció sorai: képkockák száma, ismétlés és teljes hossz; a legrövidebb és leghosszabb késleltetés.
+fn animation_text(anim: &AnimatedImage) -> (String, String) {
+    let total: std::time::Duration = anim.delays.iter().sum();
+    let plays = match anim.plays {
+        0 => "loop".to_string(),
+        1 => "once".to_string(),
+        n => format!("{}x", n),
+    };
+    let first = format!("{} frames, {}, {:.2} s", anim.total_frames, plays, total.as_secs_f32());
+    let min = anim.delays.iter().min().copied().unwrap_or_default();
+    let max = anim.delays.iter().max().copied().unwrap_or_default();
+    (first, format!("{} - {} ms", min.as_millis(), max.as_millis()))
+}
+
+/// Záridő, rekesz, ISO és gyújtótávolság egy sorban, ami az EXIF-ben megvan.
+fn exposure_text(exif: &ExifBlock) -> String {
+    let mut parts = Vec::new();
+    if let Some(t) = exif.get_num_field("ExposureTime").filter(|t| *t > 0.0) {
+        parts.push(if t < 1.0 { format!("1/{:.0} s", 1.0 / t) } else { format!("{} s", t) });
+    }
+    if let Some(f) = exif.get_num_field("FNumber") {
+        parts.push(format!("f/{:.1}", f));
+    }
+    if let Some(iso) = exif.get_num_field("ISOSpeedRatings") {
+        parts.push(format!("ISO {}", iso));
+    }
+    if let Some(focal) = exif.get_num_field("FocalLength") {
+        parts.push(format!("{:.0} mm", focal));
+    }
+    parts.join("  ")
+}
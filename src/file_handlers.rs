@@ -2,16 +2,19 @@ use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use directories::ProjectDirs;
 use std::fs;
+use std::sync::Arc;
 use std::time::SystemTime;
 use webp::Encoder;
-use image::AnimationDecoder;
-use std::io::{Read, Seek};
 use img_parts::ImageEXIF;
 use slint::ComponentHandle;
 
-use crate::exif_my::*;
 use crate::colors::*;
 use crate::image_processing::*;
+use crate::image_loader::*;
+use crate::errors::*;
+use crate::atomic_save::AtomicFile;
+use crate::modern_formats::*;
+use crate::raw_image::RAW_EXTENSIONS;
 use crate::ImageViewer;
 use crate::file_callbacks::*;
 //use crate::Pf32;
@@ -25,6 +28,26 @@ pub enum SortDir {
     Size,
 }
 
+/// Egy fájl rendezési kulcsa; a mappa listája és az animáció összeállítása is ezzel rendez.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+pub enum SortKey {
+    Name(Option<std::ffi::OsString>),
+    Ext(Option<std::ffi::OsString>),
+    Date(SystemTime),
+    Size(u64),
+}
+
+pub fn sort_key(sort: SortDir, path: &Path) -> SortKey {
+    match sort {
+        SortDir::Name => SortKey::Name(path.file_name().map(|n| n.to_os_string())),
+        SortDir::Ext => SortKey::Ext(path.extension().map(|e| e.to_os_string())),
+        SortDir::Date => SortKey::Date(path.metadata()
+            .and_then(|m| m.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH)),
+        SortDir::Size => SortKey::Size(path.metadata().map(|m| m.len()).unwrap_or(0)),
+    }
+}
+
 #[derive(PartialEq, Clone, Copy, Debug)]
 pub enum SaveFormat {
     Jpeg,
@@ -33,14 +56,23 @@ pub enum SaveFormat {
     Png,
     Bmp,
     Tif,
+    Avif,
+    Jxl,
+    Raw, // csak olvasás: DNG, CR2, NEF, ARW
+    Ico, // csak olvasás: ICO és CUR
 }
 
+/// A könyvtárlistában és a megnyitás ablakban szereplő kiterjesztések.
+pub const SUPPORTED_EXTENSIONS: [&str; 16] =
+    ["bmp", "jpg", "jpeg", "png", "tif", "tiff", "gif", "webp", "avif", "jxl", "dng", "cr2", "nef", "arw", "ico", "cur"];
+
 #[derive(Debug)]
 pub struct SaveSettings {
     pub full_path: PathBuf,
     pub saveformat: SaveFormat,
-    pub quality: u8,    // JPEG és WebP (1-100)
-    pub lossless: bool, // WebP
+    pub quality: u8,    // JPEG, WebP, AVIF és JPEG XL (1-100)
+    pub lossless: bool, // WebP és JPEG XL
+    pub effort: u8,     // AVIF és JPEG XL tömörítési ráfordítás (1-10, nagyobb: lassabb, kisebb fájl)
     pub can_include_exif: bool,
     pub include_exif: bool,
 }
@@ -52,6 +84,7 @@ pub struct RecentFileSerialized {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct AppSettings {
     pub color_settings: ColorSettings,
     pub sort_dir: SortDir,
@@ -64,6 +97,18 @@ pub struct AppSettings {
     pub bg_style: BackgroundStyle,
     pub recent_files: Vec<RecentFileSerialized>,
     pub use_gpu: bool,
+    pub prefetch_count: usize,      // ennyi képet töltünk elő mindkét irányban
+    pub prefetch_memory_mb: usize,  // az előtöltött képek memória kerete
+    pub memory_budget_mb: usize,    // a teljes memória keret (0: nincs korlát)
+    pub max_image_side: u32,        // ennél szélesebb/magasabb képet nem dekódolunk
+    pub max_decode_mb: u64,         // a dekóder legfeljebb ennyit foglalhat
+    pub keep_backup: bool,          // felülírt fájlról .bak másolat mentéskor
+    pub raw_develop: bool,          // RAW előnézet után automatikus teljes dekódolás
+    pub anim_window_mb: usize,      // animációnál a dekódolt képkockák csúszó ablaka
+    pub anim_cache_mb: usize,       // a színkorrigált képkockák cache-e lejátszáshoz
+    pub onion_opacity: f32,         // a szomszédos képkockák átlátszatlansága (onion skin)
+    pub onion_prev_tint: [u8; 3],   // az előző képkocka színezése
+    pub onion_next_tint: [u8; 3],   // a következő képkocka színezése
 }
 
 impl Default for AppSettings {
@@ -80,10 +125,117 @@ impl Default for AppSettings {
             bg_style: BackgroundStyle::DarkBright,
             recent_files: Vec::new(),
             use_gpu : true,
+            prefetch_count: 2,
+            prefetch_memory_mb: 512,
+            memory_budget_mb: 2048,
+            max_image_side: 32768,
+            max_decode_mb: 2048,
+            keep_backup: false,
+            raw_develop: false,
+            anim_window_mb: 256,
+            anim_cache_mb: 256,
+            onion_opacity: 0.3,
+            onion_prev_tint: [255, 64, 64],
+            onion_next_tint: [64, 160, 255],
         }
     }
 }
 
+impl AppSettings {
+    /// A dekódolás korlátai (decompression bomb, sérült fejléc ellen).
+    pub fn decode_limits(&self) -> image::Limits {
+        let mut limits = image::Limits::default();
+        limits.max_image_width = Some(self.max_image_side);
+        limits.max_image_height = Some(self.max_image_side);
+        limits.max_alloc = Some(self.max_decode_mb * 1024 * 1024);
+        limits
+    }
+}
+
+/// A kiterjesztés alapján megállapított formátum, ismeretlen kiterjesztésnél None.
+pub fn format_from_extension(path: &Path) -> Option<SaveFormat> {
+    let ext = path
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    match ext.as_str() {
+        "jpg" => Some(SaveFormat::Jpeg),
+        "jpeg" => Some(SaveFormat::Jpeg),
+        "webp" => Some(SaveFormat::Webp),
+        "png" => Some(SaveFormat::Png),
+        "tiff" => Some(SaveFormat::Tif),
+        "tif" => Some(SaveFormat::Tif),
+        "gif" => Some(SaveFormat::Gif),
+        "bmp" => Some(SaveFormat::Bmp),
+        "avif" => Some(SaveFormat::Avif),
+        "jxl" => Some(SaveFormat::Jxl),
+        "ico" | "cur" => Some(SaveFormat::Ico),
+        ext if RAW_EXTENSIONS.contains(&ext) => Some(SaveFormat::Raw),
+        _ => None,
+    }
+}
+
+/// A formátum a fájl első bájtjai (magic bytes) alapján.
+pub fn sniff_format(head: &[u8]) -> Option<SaveFormat> {
+    let starts = |magic: &[u8]| head.starts_with(magic);
+    if starts(b"II*\0") && head.get(8..10) == Some(&b"CR"[..]) {
+        Some(SaveFormat::Raw) // Canon CR2
+    } else if starts(&[0xFF, 0xD8, 0xFF]) {
+        Some(SaveFormat::Jpeg)
+    } else if starts(b"\x89PNG\r\n\x1a\n") {
+        Some(SaveFormat::Png)
+    } else if starts(b"GIF87a") || starts(b"GIF89a") {
+        Some(SaveFormat::Gif)
+    } else if starts(b"II*\0") || starts(b"MM\0*") {
+        Some(SaveFormat::Tif)
+    } else if starts(b"RIFF") && head.get(8..12) == Some(&b"WEBP"[..]) {
+        Some(SaveFormat::Webp)
+    } else if head.get(4..8) == Some(&b"ftyp"[..]) && matches!(head.get(8..12), Some(b"avif") | Some(b"avis")) {
+        Some(SaveFormat::Avif)
+    } else if starts(&[0xFF, 0x0A]) || starts(b"\0\0\0\x0cJXL \r\n\x87\n") {
+        Some(SaveFormat::Jxl)
+    } else if starts(b"BM") {
+        Some(SaveFormat::Bmp)
+    } else if starts(&[0, 0, 1, 0]) || starts(&[0, 0, 2, 0]) {
+        Some(SaveFormat::Ico)
+    } else {
+        None
+    }
+}
+
+/// A fájl valódi formátuma a tartalom alapján; ha nem ismerhető fel, a kiterjesztés dönt.
+pub fn detect_format(path: &Path) -> Result<SaveFormat, String> {
+    use std::io::Read;
+    let mut head = [0u8; 16];
+    let len = fs::File::open(path)
+        .and_then(|mut file| file.read(&mut head))
+        .unwrap_or(0);
+    match sniff_format(&head[..len]) {
+        // a DNG, NEF és ARW sima TIFF fejlécű, ezeknél a kiterjesztés dönt
+        Some(SaveFormat::Tif) if format_from_extension(path) == Some(SaveFormat::Raw) => Ok(SaveFormat::Raw),
+        Some(format) => Ok(format),
+        None => format_from_path(path),
+    }
+}
+
+/// A kiterjesztés alapján megállapított formátum; ismeretlen kiterjesztésnél hiba.
+pub fn format_from_path(path: &Path) -> Result<SaveFormat, String> {
+    format_from_extension(path).ok_or_else(|| {
+        let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+        format!("Unknown image format (.{})", ext)
+    })
+}
+
+/// Figyelmeztetés, ha a kiterjesztés nem egyezik a tartalom alapján felismert formátummal.
+pub fn format_mismatch(path: &Path, format: SaveFormat) -> Option<String> {
+    if format_from_extension(path) == Some(format) {
+        return None;
+    }
+    let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+    Some(format!("The .{} file is really {:?}", ext, format))
+}
+
 pub fn get_settings_path() -> PathBuf {
     if let Some(proj_dirs) = ProjectDirs::from("com", "iview", "iview-rust") {
         let config_dir = proj_dirs.config_local_dir(); // Ez az AppData/Local Windows-on
@@ -110,6 +262,18 @@ pub fn get_settings_path() -> PathBuf {
 }*/
 
 
+pub fn set_clipboard_image(img: &image::DynamicImage) -> ViewerResult<()> {
+    let rgba = img.to_rgba8();
+    let (w, h) = rgba.dimensions();
+    let image_data = arboard::ImageData {
+        width: w as usize,
+        height: h as usize,
+        bytes: std::borrow::Cow::from(rgba.into_raw()),
+    };
+    let mut cb = arboard::Clipboard::new().map_err(ViewerError::clipboard)?;
+    cb.set_image(image_data).map_err(ViewerError::clipboard)
+}
+
 impl ImageViewer {
     
     pub fn add_to_recent(&mut self, full_path: &PathBuf) {
@@ -144,57 +308,6 @@ impl ImageViewer {
         }
     }
     
-    pub fn load_animation(&mut self, path: &PathBuf) {
-        self.anim_data = None;
-        let Ok(file) = std::fs::File::open(path) else {
-            return;
-        };
-        let reader = std::io::BufReader::new(file);
-
-        // Képkockák kinyerése formátum szerint
-        let frames_result = match self.image_format {
-            SaveFormat::Gif => {
-                let decoder = image::codecs::gif::GifDecoder::new(reader).unwrap();
-                decoder.into_frames().collect_frames()
-            }
-            SaveFormat::Webp => {
-                let decoder = image::codecs::webp::WebPDecoder::new(reader).unwrap();
-                decoder.into_frames().collect_frames()
-            }
-            _ => return,
-        };
-
-        if let Ok(frames) = frames_result {
-            if frames.len() <= 1 { return; }
-            
-            let mut images = Vec::new();
-            let mut delays = Vec::new();
-
-            for (_i, frame) in frames.into_iter().enumerate() {
-                // Késleltetés kinyerése (ms)
-                let (num, den) = frame.delay().numer_denom_ms();
-                let delay_ms = if den == 0 { 100 } else { (num / den).max(20) }; // Biztonsági minimum 10ms
-                delays.push(std::time::Duration::from_millis(delay_ms as u64));
-
-                let rgba = frame.into_buffer();
-                images.push(image::DynamicImage::ImageRgba8(rgba));
-
-            }
-
-            if !images.is_empty() {
-                let total = images.len();
-                if total > 1 {
-                    self.anim_data = Some(AnimatedImage {
-                        anim_frames: images,
-                        delays,
-                        total_frames: total,
-                    });
-                    self.last_frame_time = std::time::Instant::now();
-                }
-            }
-        }
-    }
-
     pub fn save_settings(&mut self) {
         let path = get_settings_path();
         self.config.color_settings = self.color_settings;
@@ -227,92 +340,78 @@ impl ImageViewer {
                 self.same_correction_open = settings.same_correction_open;
                 self.bg_style = settings.bg_style;
                 self.config.recent_files = settings.recent_files;
+                self.config.prefetch_count = settings.prefetch_count;
+                self.config.prefetch_memory_mb = settings.prefetch_memory_mb;
+                self.config.memory_budget_mb = settings.memory_budget_mb;
+                self.config.max_image_side = settings.max_image_side;
+                self.config.max_decode_mb = settings.max_decode_mb;
+                self.config.keep_backup = settings.keep_backup;
+                self.config.raw_develop = settings.raw_develop;
+                self.config.anim_window_mb = settings.anim_window_mb;
+                self.config.anim_cache_mb = settings.anim_cache_mb;
+                self.config.onion_opacity = settings.onion_opacity;
+                self.config.onion_prev_tint = settings.onion_prev_tint;
+                self.config.onion_next_tint = settings.onion_next_tint;
                 self.recent_file_modified = true;
             }
         }
     }
 
+    /// A megjelenített (vagy eredeti) kép; a másolás csak módosításnál készül.
+    fn clipboard_image(&self) -> Option<Arc<image::DynamicImage>> {
+        let img = self.original_image.clone()?;
+        if self.save_original {
+            Some(img)
+        } else {
+            Some(Arc::new(self.image_modifies(&img)))
+        }
+    }
+
     pub fn copy_to_clipboard(&self) {
-        if let Some(mut img) = self.original_image.clone() {
-            if !self.save_original {
-                self.image_modifies(&mut img);
-            }
-            let rgba = img.to_rgba8();
-            let (w, h) = rgba.dimensions();
-            let image_data = arboard::ImageData {
-                width: w as usize,
-                height: h as usize,
-                bytes: std::borrow::Cow::from(rgba.into_raw()),
-            };
-            if let Ok(mut cb) = arboard::Clipboard::new() {
-                let _ = cb.set_image(image_data);
-            }
+        if let Some(img) = self.clipboard_image() && let Err(e) = set_clipboard_image(&img) {
+            self.notify_error(&e);
         }
     }
 
     // Kép beillesztése a vágólapról (Ctrl+V)
     pub fn copy_from_clipboard(&mut self) {
-        if let Some(temp_path) = save_clipboard_image() {
-            self.image_full_path = Some(temp_path); // nem állunk rá a tmp könyvtárra
-            self.load_image(false);
+        match save_clipboard_image() {
+            Ok(temp_path) => {
+                self.image_full_path = Some(temp_path); // nem állunk rá a tmp könyvtárra
+                self.load_image(false);
+            }
+            Err(e) => self.notify_error(&e),
         }
     }
 
     // Kép beillesztése a vágólapról (Ctrl+X)
     pub fn change_with_clipboard(&mut self) {
-        if let Some(mut img) = self.original_image.clone() {
-            if !self.save_original {
-                self.image_modifies(&mut img);
-            }
-            let rgba = img.to_rgba8().clone();
-            if let Some(temp_path) = save_clipboard_image() {
-                self.image_full_path = Some(temp_path); // nem állunk rá a tmp könyvtárra
-                self.load_image(false);
-            }
-            let (w, h) = rgba.dimensions();
-            let image_data = arboard::ImageData {
-                width: w as usize,
-                height: h as usize,
-                bytes: std::borrow::Cow::from(rgba.into_raw()),
-            };
-            if let Ok(mut cb) = arboard::Clipboard::new() {
-                let _ = cb.set_image(image_data);
+        if let Some(img) = self.clipboard_image() {
+            self.copy_from_clipboard();
+            if let Err(e) = set_clipboard_image(&img) {
+                self.notify_error(&e);
             }
         }
     }
 
-    pub fn image_modifies(&self, img: &mut image::DynamicImage) {
+    /// A mentendő kép: nagyítás, forgatás és színkorrekció az eredeti másolatán (az eredeti nem változik).
+    pub fn image_modifies(&self, img: &image::DynamicImage) -> image::DynamicImage {
         let new_width = (img.width() as f32 * self.magnify).round() as u32;
         let new_height = (img.height() as f32 * self.magnify).round() as u32;
-        let mut processed_img = if (self.magnify - 1.0).abs() > 0.001 {
-            img.resize(new_width, new_height, image::imageops::FilterType::Lanczos3)
+        let resized = if (self.magnify - 1.0).abs() > 0.001 {
+            Some(img.resize(new_width, new_height, image::imageops::FilterType::Lanczos3))
         } else {
-            img.clone()
+            None
         };
-        match self.color_settings.rotate {
-            Rotate::Rotate90 => processed_img = processed_img.rotate90(),
-            Rotate::Rotate180 => processed_img = processed_img.rotate180(),
-            Rotate::Rotate270 => processed_img = processed_img.rotate270(),
-            _ => {}
-        }
-        let mut rgba_image = processed_img.to_rgba8();
+        let mut rgba_image = rotate_and_color(resized.as_ref().unwrap_or(img), &self.color_settings, None);
         if self.color_settings.is_setted() || self.color_settings.is_blured(){
-            if let Some(interface) = &self.gpu_interface {
-                let (w, h) = rgba_image.dimensions();
-                interface.change_colorcorrection( &self.color_settings, w as f32, h as f32);
-                interface.generate_image(rgba_image.as_mut(), w, h);
-            }
-            else {
-                if let Some(lut) = &self.lut {
-                    lut.apply_lut(&mut rgba_image);
-                }
-            }
+            let (w, h) = rgba_image.dimensions();
+            self.color_correct(&self.color_settings, &mut rgba_image, w, h);
         }
-        *img = image::DynamicImage::ImageRgba8(rgba_image);
-        let bytes = img.as_bytes();
-        if bytes.iter().all(|&x| x == 0) {
+        if rgba_image.iter().all(|&x| x == 0) {
             println!("HIBA: A kép még mindig csupa nulla a módosítás után!");
         }
+        image::DynamicImage::ImageRgba8(rgba_image)
     }
 
     pub fn make_image_list(&mut self) {
@@ -328,7 +427,6 @@ impl ImageViewer {
         if folder_canonicalized != self.image_folder {
             // Új image_folder mentése
             self.image_folder = folder_canonicalized.clone();
-            let supported_extensions = ["bmp", "jpg", "jpeg", "png", "tif", "gif", "webp"];
             // Lista ürítése és újratöltése
             self.list_of_images.clear();
             if let Some(p) = &self.image_folder {
@@ -338,7 +436,7 @@ impl ImageViewer {
 
                         if full_path.is_file() {
                             if let Some(ext) = full_path.extension().and_then(|s| s.to_str()) {
-                                if supported_extensions.contains(&ext.to_lowercase().as_str()) {
+                                if SUPPORTED_EXTENSIONS.contains(&ext.to_lowercase().as_str()) {
                                     self.list_of_images.push(entry);
                                 }
                             }
@@ -348,27 +446,8 @@ impl ImageViewer {
             }
         }
 
-        match self.sort {
-            SortDir::Name => {
-                self.list_of_images
-                    .sort_by_key(|p| p.file_name().to_os_string());
-            }
-            SortDir::Ext => {
-                self.list_of_images
-                    .sort_by_key(|p| p.path().extension().unwrap().to_os_string());
-            }
-            SortDir::Date => {
-                self.list_of_images.sort_by_key(|p| {
-                    p.metadata()
-                        .and_then(|m| m.modified())
-                        .unwrap_or(SystemTime::UNIX_EPOCH)
-                });
-            }
-            SortDir::Size => {
-                self.list_of_images
-                    .sort_by_key(|p| p.metadata().map(|m| m.len()).unwrap_or(0));
-            }
-        }
+        let sort = self.sort;
+        self.list_of_images.sort_by_cached_key(|entry| sort_key(sort, &entry.path()));
 
         if let Some(actual) = &self.image_full_path {
             if let Ok(actual_canonicalized) = fs::canonicalize(actual) {
@@ -414,6 +493,8 @@ impl ImageViewer {
                 .add_filter("Tiff", &["tif"])
                 .add_filter("Gif", &["gif"])
                 .add_filter("Webp", &["webp"])
+                .add_filter("Avif", &["avif"])
+                .add_filter("JPEG XL", &["jxl"])
                 .add_filter("Windows bitmap", &["bmp"])
                 .set_file_name(&default_save_name); // Alapértelmezett név
 
@@ -436,18 +517,22 @@ impl ImageViewer {
                     "tif" => SaveFormat::Tif,
                     "gif" => SaveFormat::Gif,
                     "bmp" => SaveFormat::Bmp,
+                    "avif" => SaveFormat::Avif,
+                    "jxl" => SaveFormat::Jxl,
                     &_ => SaveFormat::Png,
                 };
-                let inex = self.exif.is_some();
-                let can = ( saveformat == SaveFormat::Jpeg || saveformat == SaveFormat::Webp
-                    || saveformat == SaveFormat::Bmp ) && inex;
-                let mut dial_need = saveformat == SaveFormat::Jpeg || saveformat == SaveFormat::Webp ||
-                    (saveformat == SaveFormat::Bmp && inex);
+                // a RAW metaadata (MakerNote, nyers IFD-k) nem fér el egy JPEG EXIF szegmensben
+                let inex = self.exif.is_some() && self.image_format != SaveFormat::Raw;
+                let with_quality = matches!(saveformat,
+                    SaveFormat::Jpeg | SaveFormat::Webp | SaveFormat::Avif | SaveFormat::Jxl);
+                let can = ( with_quality || saveformat == SaveFormat::Bmp ) && inex;
+                let dial_need = with_quality || (saveformat == SaveFormat::Bmp && inex);
                 self.save_dialog = Some(SaveSettings {
                     full_path: ut,
                     saveformat,
                     quality: 85, // Alapértelmezett JPEG minőség
                     lossless: false,
+                    effort: DEFAULT_EFFORT,
                     can_include_exif: can,
                     include_exif: inex,
                 });
@@ -458,6 +543,7 @@ impl ImageViewer {
                             s_ui.set_saveformat(saveformat as i32);
                             s_ui.set_quality(85.0);
                             s_ui.set_lossless(false);
+                            s_ui.set_effort(DEFAULT_EFFORT as f32);
                             s_ui.set_can_include_exif(can);
                             s_ui.set_include_exif(inex);
                             if let Some(exif) = self.exif.clone() {
@@ -478,225 +564,267 @@ impl ImageViewer {
 
     pub fn completing_save(&mut self) {
         if let Some(save_data) = self.save_dialog.take() {
-            self.add_to_recent(&save_data.full_path);
-            if let Some(mut img) = self.original_image.clone() {
-                let mut resolution = self.resolution.clone();
-                if !self.save_original {
-                    if let Some(mut resol) = resolution.clone() {
-                        resol.xres *= self.magnify;
-                        resol.yres *= self.magnify;
-                        resolution = Some(resol);
-                    }                    
-                    self.image_modifies(&mut img);
-                }
-                match save_data.saveformat {
-                    SaveFormat::Jpeg => {
-                        let mut buffer = Vec::new();
-                        let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut buffer, save_data.quality);
-                        img.write_with_encoder(encoder).expect("JPEG kódolási hiba");
-                        if let Ok(mut jpeg) = img_parts::jpeg::Jpeg::from_bytes(buffer.into()) {
-                            if let Some(res) = resolution.clone() {
-                                let dpi_unit = if res.dpi { 1u8 } else { 2u8 }; 
-                                let x_res = res.xres as u16;
-                                let y_res = res.yres as u16;
-                                // JFIF APP0 adatok
-                                let jfif_data = vec![
-                                    b'J', b'F', b'I', b'F', 0,
-                                    1, 1,
-                                    dpi_unit,
-                                    (x_res >> 8) as u8, (x_res & 0xFF) as u8,
-                                    (y_res >> 8) as u8, (y_res & 0xFF) as u8,
-                                    0, 0,
-                                ];
-                                let new_seg = img_parts::jpeg::JpegSegment::new_with_contents(
-                                    0xE0, 
-                                    img_parts::Bytes::from(jfif_data)
-                                );
-                                // APP0 (0xE0) keresése és frissítése
-                                let app0_pos = jpeg.segments().iter().position(|s| s.marker() == 0xE0);
-                                if let Some(pos) = app0_pos {
-                                    jpeg.segments_mut()[pos] = new_seg;
-                                } else {
-                                    jpeg.segments_mut().insert(0, new_seg);
-                                }
-                            }
-                            if let (true, Some(mut exif)) = (save_data.include_exif, self.exif.clone()) {
-                                let rot = exif.get_num_field("Orientation").unwrap_or(1.0);
-                                if !self.save_original || rot != 1.0 {
-                                    if let Some(res) = resolution.clone() {
-                                        let thumbnail = exif.generate_fitted_thumbnail(&img.to_rgba8());
-                                        exif.patch_thumbnail(&thumbnail);
-                                        exif.patch_exifdata( res.xres, res.yres, self.image_size.x as u32, self.image_size.y as u32);
-                                    }
-                                }
-                                let exif_segment = img_parts::jpeg::JpegSegment::new_with_contents(
-                                    0xE1, 
-                                    img_parts::Bytes::from(exif.raw_exif.clone())
-                                );
-                                jpeg.segments_mut().insert(1, exif_segment);
-                            }
-                            let file = std::fs::File::create(&save_data.full_path).unwrap();
-                            jpeg.encoder().write_to(file).expect("Fájlírási hiba");
-                        }
-                    }
-                    SaveFormat::Webp => {
-                        let encoder =
-                            Encoder::from_image(&img).expect("Hiba a WebP enkóder létrehozásakor");
-                        let memory = if save_data.lossless {
-                            encoder.encode_lossless()
+            if self.anim_data.is_some() && matches!(save_data.saveformat, SaveFormat::Gif | SaveFormat::Webp) {
+                self.save_animation(save_data);
+                return;
+            }
+            match self.write_image(&save_data) {
+                Ok(()) => self.saved(&save_data.full_path),
+                Err(e) => self.notify_error(&e),
+            }
+        }
+    }
+
+    /// Sikeres mentés után: a legutóbbi fájlok közé kerül, és ha látszik, újratöltődik.
+    pub fn saved(&mut self, path: &PathBuf) {
+        self.add_to_recent(path);
+        self.prefetch.take(path); // ha előtöltött szomszéd volt, az már elavult
+        self.reload_if_shown(path);
+    }
+
+    /// Ha a látható képet írtuk felül, újratöltjük. A módosítással mentett kép már tartalmazza
+    /// a színkorrekciót, forgatást és nagyítást, ezért azok alaphelyzetbe állnak.
+    fn reload_if_shown(&mut self, saved: &Path) {
+        let same = match (&self.shown_image, fs::canonicalize(saved)) {
+            (Some(shown), Ok(saved)) => fs::canonicalize(shown).is_ok_and(|shown| shown == saved),
+            _ => false,
+        };
+        if !same {
+            return;
+        }
+        if !self.save_original {
+            self.color_settings = ColorSettings::default();
+            self.magnify = 1.0;
+        }
+        self.image_full_path = self.shown_image.clone();
+        if let Ok(format) = detect_format(saved) {
+            self.image_format = format;
+        }
+        self.load_image(true);
+    }
+
+    /// A mentendő EXIF blokk ("Exif\0\0" fejléccel); módosított képnél a bélyegkép és a méretek frissülnek.
+    fn exif_for_save(&self, save_data: &SaveSettings, img: &image::DynamicImage, resolution: &Option<Resolution>) -> Option<Vec<u8>> {
+        if !save_data.include_exif {
+            return None;
+        }
+        let mut exif = self.exif.clone()?;
+        let rot = exif.get_num_field("Orientation").unwrap_or(1.0);
+        if (!self.save_original || rot != 1.0) && let Some(res) = resolution {
+            let thumbnail = exif.generate_fitted_thumbnail(&img.to_rgba8());
+            exif.patch_thumbnail(&thumbnail);
+            exif.patch_exifdata( res.xres, res.yres, self.image_size.x as u32, self.image_size.y as u32);
+        }
+        Some(exif.raw_exif)
+    }
+
+    /// A kép kódolása és kiírása a mentési beállítások szerint; hibánál a fájl nevét és az okot adja vissza.
+    fn write_image(&self, save_data: &SaveSettings) -> ViewerResult<()> {
+        let target = &save_data.full_path;
+        let save_err = |e: &dyn std::fmt::Display| ViewerError::save(target, e);
+        let Some(mut img) = self.original_image.clone() else {
+            return Err(save_err(&"no image"));
+        };
+        // minden ág az ideiglenes fájlba ír, a cél csak a végén cserélődik
+        let atomic = AtomicFile::new(target);
+        let path = atomic.temp_path();
+        let mut resolution = self.resolution.clone();
+        if !self.save_original {
+            if let Some(mut resol) = resolution.clone() {
+                resol.xres *= self.magnify;
+                resol.yres *= self.magnify;
+                resolution = Some(resol);
+            }                    
+            img = Arc::new(self.image_modifies(&img));
+        }
+        match save_data.saveformat {
+            SaveFormat::Jpeg => {
+                let mut buffer = Vec::new();
+                let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut buffer, save_data.quality);
+                img.write_with_encoder(encoder).map_err(|e| save_err(&e))?;
+                {
+                    let mut jpeg = img_parts::jpeg::Jpeg::from_bytes(buffer.into()).map_err(|e| save_err(&e))?;
+                    if let Some(res) = resolution.clone() {
+                        let dpi_unit = if res.dpi { 1u8 } else { 2u8 }; 
+                        let x_res = res.xres as u16;
+                        let y_res = res.yres as u16;
+                        // JFIF APP0 adatok
+                        let jfif_data = vec![
+                            b'J', b'F', b'I', b'F', 0,
+                            1, 1,
+                            dpi_unit,
+                            (x_res >> 8) as u8, (x_res & 0xFF) as u8,
+                            (y_res >> 8) as u8, (y_res & 0xFF) as u8,
+                            0, 0,
+                        ];
+                        let new_seg = img_parts::jpeg::JpegSegment::new_with_contents(
+                            0xE0, 
+                            img_parts::Bytes::from(jfif_data)
+                        );
+                        // APP0 (0xE0) keresése és frissítése
+                        let app0_pos = jpeg.segments().iter().position(|s| s.marker() == 0xE0);
+                        if let Some(pos) = app0_pos {
+                            jpeg.segments_mut()[pos] = new_seg;
                         } else {
-                            encoder.encode(save_data.quality as f32)
-                        };
-                        let mut webp = img_parts::webp::WebP::from_bytes(img_parts::Bytes::copy_from_slice(&*memory))
-                            .expect("Hiba a WebP struktúra feldolgozásakor");
-                        if let (true, Some(mut exif)) = (save_data.include_exif, self.exif.clone()) {
-                            let rot = exif.get_num_field("Orientation").unwrap_or(1.0);
-                            if !self.save_original || rot != 1.0 {
-                                if let Some(res) = resolution.clone() {
-                                    let thumbnail = exif.generate_fitted_thumbnail(&img.to_rgba8());
-                                    exif.patch_thumbnail(&thumbnail);
-                                    exif.patch_exifdata( res.xres, res.yres, self.image_size.x as u32, self.image_size.y as u32);
-                                }
-                            }
-                            webp.set_exif(Some(img_parts::Bytes::from(exif.raw_exif)));
-                        }
-                        let file = std::fs::File::create(&save_data.full_path).expect("Fájl létrehozási hiba");
-                        if let Err(e) = webp.encoder().write_to(file) {
-                            println!("Hiba a WebP fájl írásakor: {}", e);
+                            jpeg.segments_mut().insert(0, new_seg);
                         }
                     }
-                    SaveFormat::Tif => {
-                        let file = std::fs::File::create(&save_data.full_path).unwrap();
-                        let rgb_data = img.to_rgba8(); 
-                        let (x, y, unit) = if let Some(res) = resolution {
-                            ((res.xres * 1000.0) as u32, (res.yres * 1000.0) as u32, if res.dpi { 2u16 } else { 3u16 })
-                        } else {
-                            (72000, 72000, 2u16)
-                        };
-                        let mut tiff_writer = tiff::encoder::TiffEncoder::new(file)
-                            .unwrap()
-                            .with_compression(tiff::encoder::Compression::Deflate(tiff::encoder::DeflateLevel::Best));
-                        let mut col = tiff_writer.new_image::<tiff::encoder::colortype::RGBA8>(img.width(), img.height()).unwrap();
-
-                        col.encoder().write_tag(tiff::tags::Tag::XResolution, tiff::encoder::Rational { n: x, d: 1000 }).unwrap();
-                        col.encoder().write_tag(tiff::tags::Tag::YResolution, tiff::encoder::Rational { n: y, d: 1000 }).unwrap();
-                        col.encoder().write_tag(tiff::tags::Tag::ResolutionUnit, unit).unwrap();
-                        col.encoder().write_tag(tiff::tags::Tag::Software, "IView 2026").unwrap();
-                        col.encoder().write_tag(tiff::tags::Tag::DateTime, chrono::Local::now().format("%Y:%m:%d %H:%M:%S").to_string().as_str()).unwrap();
-
-                        col.write_data(rgb_data.as_raw()).expect("TIFF írási hiba");
+                    if let Some(exif) = self.exif_for_save(save_data, &img, &resolution) {
+                        let exif_segment = img_parts::jpeg::JpegSegment::new_with_contents(
+                            0xE1, 
+                            img_parts::Bytes::from(exif)
+                        );
+                        jpeg.segments_mut().insert(1, exif_segment);
                     }
-                    SaveFormat::Png => {
-                        let mut buffer = Vec::new();
-                        {
-                            let mut png_encoder = png::Encoder::new(&mut buffer, img.width(), img.height());
-                            let color_type = match img.color() {
-                                image::ColorType::Rgb8 => png::ColorType::Rgb,
-                                image::ColorType::Rgba8 => png::ColorType::Rgba,
-                                _ => png::ColorType::Rgba,
-                            };
-                            png_encoder.set_color(color_type);
-                            png_encoder.set_depth(png::BitDepth::Eight);
-                            if let Some(res) = resolution {
-                                let (dpm_x, dpm_y) = if res.dpi {
-                                    ((res.xres / 0.0254 + 0.5) as u32, (res.yres / 0.0254 + 0.5) as u32)
-                                } else {
-                                    ((res.xres * 100.0 + 0.5) as u32, (res.yres * 100.0 + 0.5) as u32)
-                                };
-                                png_encoder.set_pixel_dims(Some(png::PixelDimensions {
-                                    xppu: dpm_x, yppu: dpm_y, unit: png::Unit::Meter, }));
-                            }
-                            let mut writer = png_encoder.write_header().unwrap();
-                            writer.write_image_data(img.as_bytes()).expect("PNG adatírási hiba");
-                        }
+                    let file = std::fs::File::create(path).map_err(|e| save_err(&e))?;
+                    jpeg.encoder().write_to(file).map_err(|e| save_err(&e))?;
+                }
+            }
+            SaveFormat::Webp => {
+                let encoder =
+                    Encoder::from_image(&img).map_err(|e| save_err(&e))?;
+                let memory = if save_data.lossless {
+                    encoder.encode_lossless()
+                } else {
+                    encoder.encode(save_data.quality as f32)
+                };
+                let mut webp = img_parts::webp::WebP::from_bytes(img_parts::Bytes::copy_from_slice(&memory))
+                    .map_err(|e| save_err(&e))?;
+                if let Some(exif) = self.exif_for_save(save_data, &img, &resolution) {
+                    webp.set_exif(Some(img_parts::Bytes::from(exif)));
+                }
+                let file = std::fs::File::create(path).map_err(|e| save_err(&e))?;
+                webp.encoder().write_to(file).map_err(|e| save_err(&e))?;
+            }
+            SaveFormat::Tif => {
+                let file = std::fs::File::create(path).map_err(|e| save_err(&e))?;
+                let rgb_data = img.to_rgba8(); 
+                let (x, y, unit) = if let Some(res) = resolution {
+                    ((res.xres * 1000.0) as u32, (res.yres * 1000.0) as u32, if res.dpi { 2u16 } else { 3u16 })
+                } else {
+                    (72000, 72000, 2u16)
+                };
+                let mut tiff_writer = tiff::encoder::TiffEncoder::new(file)
+                    .map_err(|e| save_err(&e))?
+                    .with_compression(tiff::encoder::Compression::Deflate(tiff::encoder::DeflateLevel::Best));
+                let mut col = tiff_writer.new_image::<tiff::encoder::colortype::RGBA8>(img.width(), img.height())
+                    .map_err(|e| save_err(&e))?;
+
+                col.encoder().write_tag(tiff::tags::Tag::XResolution, tiff::encoder::Rational { n: x, d: 1000 }).map_err(|e| save_err(&e))?;
+                col.encoder().write_tag(tiff::tags::Tag::YResolution, tiff::encoder::Rational { n: y, d: 1000 }).map_err(|e| save_err(&e))?;
+                col.encoder().write_tag(tiff::tags::Tag::ResolutionUnit, unit).map_err(|e| save_err(&e))?;
+                col.encoder().write_tag(tiff::tags::Tag::Software, "IView 2026").map_err(|e| save_err(&e))?;
+                col.encoder().write_tag(tiff::tags::Tag::DateTime, chrono::Local::now().format("%Y:%m:%d %H:%M:%S").to_string().as_str()).map_err(|e| save_err(&e))?;
 
-                        if let (true, Some(exif)) = (save_data.include_exif, self.exif.clone()) {
-                            let clean_exif = exif.raw_exif[6..].to_vec();
-                            let mut png_parts = img_parts::png::Png::from_bytes(buffer.into()).unwrap();
-                            let exif_chunk = img_parts::png::PngChunk::new(*b"eXIf", img_parts::Bytes::copy_from_slice(&clean_exif));
-                            let pos = png_parts.chunks().len() - 1;
-                            png_parts.chunks_mut().insert(pos, exif_chunk);
-                            let file = std::fs::File::create(&save_data.full_path).unwrap();
-                            png_parts.encoder().write_to(file).expect("PNG fájlmentési hiba");
+                col.write_data(rgb_data.as_raw()).map_err(|e| save_err(&e))?;
+            }
+            SaveFormat::Png => {
+                let mut buffer = Vec::new();
+                {
+                    let mut png_encoder = png::Encoder::new(&mut buffer, img.width(), img.height());
+                    let color_type = match img.color() {
+                        image::ColorType::Rgb8 => png::ColorType::Rgb,
+                        image::ColorType::Rgba8 => png::ColorType::Rgba,
+                        _ => png::ColorType::Rgba,
+                    };
+                    png_encoder.set_color(color_type);
+                    png_encoder.set_depth(png::BitDepth::Eight);
+                    if let Some(res) = &resolution {
+                        let (dpm_x, dpm_y) = if res.dpi {
+                            ((res.xres / 0.0254 + 0.5) as u32, (res.yres / 0.0254 + 0.5) as u32)
                         } else {
-                            std::fs::write(&save_data.full_path, buffer).unwrap();
-                        }
-                    }
-                    
-                    SaveFormat::Bmp => {
-                        let mut buffer = std::io::Cursor::new(Vec::new());
-                        img.write_to(&mut buffer, image::ImageFormat::Bmp)
-                            .expect("Hiba a BMP kódolásakor");
-                        let mut bmp_data = buffer.into_inner();
-                        if let Some(res) = resolution.clone() {
-                            let (dpm_x, dpm_y) = if res.dpi {
-                                ((res.xres / 0.0254 + 0.5) as u32, (res.yres / 0.0254 + 0.5) as u32)
-                            } else {
-                                ((res.xres * 100.0 + 0.5) as u32, (res.yres * 100.0 + 0.5) as u32)
-                            };
-                            let dpm_x_bytes = dpm_x.to_le_bytes();
-                            let dpm_y_bytes = dpm_y.to_le_bytes();
-                            if bmp_data.len() > 46 {
-                                bmp_data[38..42].copy_from_slice(&dpm_x_bytes);
-                                bmp_data[42..46].copy_from_slice(&dpm_y_bytes);
-                            }
-                        }
-                        if let (true, Some(mut exif)) = (save_data.include_exif, self.exif.clone()) {
-                            let rot = exif.get_num_field("Orientation").unwrap_or(1.0);
-                            if !self.save_original || rot != 1.0 {
-                                if let Some(res) = resolution.clone() {
-                                    let thumbnail = exif.generate_fitted_thumbnail(&img.to_rgba8());
-                                    exif.patch_thumbnail(&thumbnail);
-                                    exif.patch_exifdata( res.xres, res.yres, self.image_size.x as u32, self.image_size.y as u32);
-                                }
-                            }
-                            let original_pixel_offset = u32::from_le_bytes(bmp_data[10..14].try_into().unwrap()) as usize;
-                            let exif_to_insert = exif.raw_exif.clone();
-                            // 2026-os tipp: A BMP-be érdemes egy extra 4 bájtos hosszt vagy azonosítót 
-                            // tenni az EXIF elé, de a nyers "Exif\0\0" is megteszi.
-                            let mut new_bmp = Vec::with_capacity(bmp_data.len() + exif_to_insert.len());
-                            new_bmp.extend_from_slice(&bmp_data[..original_pixel_offset]);
-                            new_bmp.extend_from_slice(&exif_to_insert);
-                            new_bmp.extend_from_slice(&bmp_data[original_pixel_offset..]);
-                            let new_pixel_offset = (original_pixel_offset + exif_to_insert.len()) as u32;
-                            let new_file_size = new_bmp.len() as u32;
-                            new_bmp[2..6].copy_from_slice(&new_file_size.to_le_bytes());
-                            new_bmp[10..14].copy_from_slice(&new_pixel_offset.to_le_bytes());
-                            bmp_data = new_bmp;
-                        }
-                        std::fs::write(&save_data.full_path, bmp_data)
-                            .expect("Hiba a BMP fájl mentésekor");
+                            ((res.xres * 100.0 + 0.5) as u32, (res.yres * 100.0 + 0.5) as u32)
+                        };
+                        png_encoder.set_pixel_dims(Some(png::PixelDimensions {
+                            xppu: dpm_x, yppu: dpm_y, unit: png::Unit::Meter, }));
                     }
+                    let mut writer = png_encoder.write_header().map_err(|e| save_err(&e))?;
+                    writer.write_image_data(img.as_bytes()).map_err(|e| save_err(&e))?;
+                }
 
-                    SaveFormat::Gif => {
-                        if let Err(e) = img.save(&save_data.full_path) {
-                            println!("Hiba a mentéskor ({:?}): {}", save_data.saveformat, e);
-                        }
+                if let Some(exif) = self.exif_for_save(save_data, &img, &resolution) {
+                    let clean_exif = exif.get(6..).unwrap_or_default().to_vec();
+                    let mut png_parts = img_parts::png::Png::from_bytes(buffer.into()).map_err(|e| save_err(&e))?;
+                    let exif_chunk = img_parts::png::PngChunk::new(*b"eXIf", img_parts::Bytes::copy_from_slice(&clean_exif));
+                    let pos = png_parts.chunks().len().saturating_sub(1);
+                    png_parts.chunks_mut().insert(pos, exif_chunk);
+                    let file = std::fs::File::create(path).map_err(|e| save_err(&e))?;
+                    png_parts.encoder().write_to(file).map_err(|e| save_err(&e))?;
+                } else {
+                    std::fs::write(path, buffer).map_err(|e| save_err(&e))?;
+                }
+            }
+            
+            SaveFormat::Bmp => {
+                let mut buffer = std::io::Cursor::new(Vec::new());
+                img.write_to(&mut buffer, image::ImageFormat::Bmp)
+                    .map_err(|e| save_err(&e))?;
+                let mut bmp_data = buffer.into_inner();
+                if let Some(res) = resolution.clone() {
+                    let (dpm_x, dpm_y) = if res.dpi {
+                        ((res.xres / 0.0254 + 0.5) as u32, (res.yres / 0.0254 + 0.5) as u32)
+                    } else {
+                        ((res.xres * 100.0 + 0.5) as u32, (res.yres * 100.0 + 0.5) as u32)
+                    };
+                    let dpm_x_bytes = dpm_x.to_le_bytes();
+                    let dpm_y_bytes = dpm_y.to_le_bytes();
+                    if bmp_data.len() > 46 {
+                        bmp_data[38..42].copy_from_slice(&dpm_x_bytes);
+                        bmp_data[42..46].copy_from_slice(&dpm_y_bytes);
                     }
                 }
-                
+                if let Some(exif_to_insert) = self.exif_for_save(save_data, &img, &resolution) {
+                    let original_pixel_offset = bmp_data.get(10..14)
+                        .and_then(|b| b.try_into().ok())
+                        .map(u32::from_le_bytes)
+                        .filter(|&offset| (offset as usize) <= bmp_data.len())
+                        .ok_or_else(|| save_err(&"invalid BMP header"))? as usize;
+                    // 2026-os tipp: A BMP-be érdemes egy extra 4 bájtos hosszt vagy azonosítót 
+                    // tenni az EXIF elé, de a nyers "Exif\0\0" is megteszi.
+                    let mut new_bmp = Vec::with_capacity(bmp_data.len() + exif_to_insert.len());
+                    new_bmp.extend_from_slice(&bmp_data[..original_pixel_offset]);
+                    new_bmp.extend_from_slice(&exif_to_insert);
+                    new_bmp.extend_from_slice(&bmp_data[original_pixel_offset..]);
+                    let new_pixel_offset = (original_pixel_offset + exif_to_insert.len()) as u32;
+                    let new_file_size = new_bmp.len() as u32;
+                    new_bmp[2..6].copy_from_slice(&new_file_size.to_le_bytes());
+                    new_bmp[10..14].copy_from_slice(&new_pixel_offset.to_le_bytes());
+                    bmp_data = new_bmp;
+                }
+                std::fs::write(path, bmp_data).map_err(|e| save_err(&e))?;
+            }
+
+            SaveFormat::Gif => {
+                img.save_with_format(path, image::ImageFormat::Gif).map_err(|e| save_err(&e))?;
+            }
+            SaveFormat::Avif => {
+                let exif = self.exif_for_save(save_data, &img, &resolution);
+                let data = encode_avif(&img, save_data.quality, save_data.effort, exif.as_deref())
+                    .map_err(|e| save_err(&e))?;
+                std::fs::write(path, data).map_err(|e| save_err(&e))?;
+            }
+            SaveFormat::Jxl => {
+                let exif = self.exif_for_save(save_data, &img, &resolution);
+                let data = encode_jxl(&img, save_data.quality, save_data.lossless, save_data.effort, exif.as_deref())
+                    .map_err(|e| save_err(&e))?;
+                std::fs::write(path, data).map_err(|e| save_err(&e))?;
+            }
+            SaveFormat::Raw | SaveFormat::Ico => {
+                return Err(save_err(&format!("{:?} files can only be read", save_data.saveformat)));
             }
         }
+        atomic.commit(self.config.keep_backup).map_err(|e| save_err(&e))
     }
 
     pub fn open_image(&mut self, path: &PathBuf, make_list: bool) {
-        self.image_full_path = Some(path.clone());
-        let ext = path
-            .extension()
-            .and_then(|s| s.to_str())
-            .unwrap_or("")
-            .to_lowercase();
-        let image_format = match ext.as_str() {
-            "jpg" => SaveFormat::Jpeg,
-            "jpeg" => SaveFormat::Jpeg,
-            "webp" => SaveFormat::Webp,
-            "png" => SaveFormat::Png,
-            "tiff" => SaveFormat::Tif,
-            "tif" => SaveFormat::Tif,
-            "gif" => SaveFormat::Gif,
-            _ => SaveFormat::Bmp,
+        self.image_format = match detect_format(path) {
+            Ok(format) => format,
+            Err(e) => {
+                self.notify_error(&ViewerError::load(path, e));
+                return;
+            }
         };
-        self.image_format = image_format;
+        self.image_full_path = Some(path.clone());
         if make_list {
             self.add_to_recent(&path);
             self.make_image_list();
@@ -706,15 +834,16 @@ impl ImageViewer {
 
     pub fn open_image_dialog(&mut self, def: &Option<PathBuf>) {
         let mut dialog = rfd::FileDialog::new()
-            .add_filter(
-                "Images",
-                &["bmp", "jpg", "jpeg", "png", "tif", "tiff", "gif", "webp"],
-            )
+            .add_filter("Images", &SUPPORTED_EXTENSIONS)
             .add_filter("Png", &["png"])
             .add_filter("Jpeg kép", &["jpg", "jpeg"])
             .add_filter("Webp", &["webp"])
             .add_filter("Tiff", &["tif", "tiff"])
             .add_filter("Gif", &["gif"])
+            .add_filter("Avif", &["avif"])
+            .add_filter("JPEG XL", &["jxl"])
+            .add_filter("Camera RAW", &RAW_EXTENSIONS)
+            .add_filter("Icon", &["ico", "cur"])
             .add_filter("Windows bitmap", &["bmp"]);
 
         if let Some(path) = def {
@@ -736,295 +865,199 @@ impl ImageViewer {
         }
     }
 
+    /// Az új kép első megjelenítéséhez használt színbeállítások.
+    fn load_color_settings(&self) -> ColorSettings {
+        if self.same_correction_open {
+            self.effective_color_settings()
+        } else {
+            ColorSettings::default()
+        }
+    }
+
     pub fn load_image(&mut self, reopen: bool) {
         let Some(filepath) = self.image_full_path.clone() else {
             return;
         };
-        self.resolution = None;
         self.anim_timer.stop();
-        if let Ok(mut img) = image::open(&filepath) {
-            if self.image_format == SaveFormat::Tif {
-                if let Ok(file) = std::fs::File::open(&filepath) {
-                    if let Ok(mut decoder) = tiff::decoder::Decoder::new(file) {
-                        if let Ok(tiff::decoder::ifd::Value::Rational(n, d)) =
-                            decoder.get_tag(tiff::tags::Tag::XResolution)
-                        {
-                            let xres = n as f32 / d as f32;
-                            if let Ok(tiff::decoder::ifd::Value::Rational(n, d)) =
-                                decoder.get_tag(tiff::tags::Tag::YResolution)
-                            {
-                                let yres = n as f32 / d as f32;
-                                if let Ok(unit) = decoder.get_tag(tiff::tags::Tag::ResolutionUnit) {
-                                    let dpi = unit == tiff::decoder::ifd::Value::Unsigned(2);
-                                    self.resolution = Some(Resolution { xres, yres, dpi });
-                                    //println!("{:?} {:?} {:?} ",xres,yres,unit);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-            else if self.image_format == SaveFormat::Bmp {
-                if let Ok(mut file) = std::fs::File::open(&filepath) {
-                    let mut buffer = [0u8; 8];
-                    if file.seek(std::io::SeekFrom::Start(38)).is_ok()
-                        && file.read_exact(&mut buffer).is_ok()
-                    {
-                        let x_ppm = u32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
-                        let y_ppm = u32::from_le_bytes([buffer[4], buffer[5], buffer[6], buffer[7]]);
-                        if x_ppm > 0 && y_ppm > 0 {
-                            let xres = (x_ppm as f32 / 39.3701).round();
-                            let yres = (y_ppm as f32 / 39.3701).round();
-                            self.resolution = Some(Resolution {
-                                xres,
-                                yres,
-                                dpi: true,
-                            });
-                        }
-                    }
-                }
-            }
-            else if self.image_format == SaveFormat::Png {
-                if let Ok(file) = std::fs::File::open(&filepath) {
-                    let reader = std::io::BufReader::new(file);
-                    let decoder = png::Decoder::new(reader);
-                    if let Ok(reader) = decoder.read_info() {
-                        if let Some(phys) = reader.info().pixel_dims {
-                            if phys.unit == png::Unit::Meter {
-                                let x_ppm = phys.xppu;
-                                let y_ppm = phys.yppu;
-                                let xres = (x_ppm as f32 / 39.3701).round();
-                                let yres = (y_ppm as f32 / 39.3701).round();
-                                self.resolution = Some(Resolution {
-                                    xres,
-                                    yres,
-                                    dpi: true,
-                                });
-                            }
-                        }
-                    }
-                }
-            }
-            else if self.image_format == SaveFormat::Jpeg {
-                if let Ok(mut file) = std::fs::File::open(&filepath) {
-                    let mut header = [0u8; 18];
-                    if file.read_exact(&mut header).is_ok() {
-                        // Ellenőrizzük a JFIF mágiát: [FF D8 FF E0 ... 'J' 'F' 'I' 'F']
-                        if header[0..4] == [0xFF, 0xD8, 0xFF, 0xE0] && &header[6..10] == b"JFIF" {
-                            let unit = header[13]; // 1 = DPI (dots per inch), 2 = DPC (dots per cm)
-                            let xres = u16::from_be_bytes([header[14], header[15]]) as f32;
-                            let yres = u16::from_be_bytes([header[16], header[17]]) as f32;
-                            if xres > 0.0 && yres > 0.0 && (unit == 1 || unit == 2) {
-                                self.resolution = Some(Resolution {
-                                    xres,
-                                    yres,
-                                    dpi: unit == 1,
-                                });
-                            }
-                        }
-                    }
-                }
-            }
+        if let Some(file_name) = filepath.file_name().and_then(|n| n.to_str()) {
+            self.image_name = file_name.to_string();
+        }
+        if !reopen && let Some(loaded) = self.prefetch.take(&filepath) {
+            self.loader.cancel();
+            self.show_loading(false);
+            self.apply_loaded(filepath, reopen, loaded);
+            return;
+        }
+        // új fájl az első oldallal nyílik, újratöltésnél az oldal marad
+        let page = if reopen { self.current_page } else { 0 };
+        self.start_load(filepath, reopen, false, page);
+    }
 
-            if let Ok(metadata) = fs::metadata(&filepath) { // for file size & date
-                self.file_meta = Some(metadata);
-            } else {
-                self.file_meta = None;
-            }
+    pub fn start_load(&mut self, filepath: PathBuf, reopen: bool, raw_develop: bool, page: usize) {
+        let request = LoadRequest {
+            path: filepath,
+            format: self.image_format,
+            reopen,
+            color_settings: self.load_color_settings(),
+            max_texture_size: self.max_texture_size,
+            limits: self.config.decode_limits(),
+            raw_develop,
+            page,
+            anim_window_bytes: self.config.anim_window_mb * 1024 * 1024,
+        };
+        self.loader.start(request, self.ui_handle.clone());
+        self.show_loading(true);
+    }
 
-            self.exif = None;
-            if let Ok(mut f) = std::fs::File::open(&filepath) {
-                let mut buffer = Vec::new();
-                if f.read_to_end(&mut buffer).is_ok() {
-                    if self.image_format == SaveFormat::Webp {
-                        if let Ok(webp) = img_parts::webp::WebP::from_bytes(buffer.clone().into()) {
-                            if let Some(exif_bytes) = webp.exif() {
-                                let mut data = exif_bytes.to_vec().clone();
-                                if !data.starts_with(b"Exif\0\0") {
-                                    let mut legacy_format = b"Exif\0\0".to_vec();
-                                    legacy_format.extend_from_slice(&data);
-                                    data = legacy_format;
-                                }
-                                let mut exifblock = ExifBlock::default();
-                                let len = data.len();
-                                if let Ok(result) = exifblock.open( &data, len) {
-                                    let mut res = Resolution { xres:0.0, yres:0.0, dpi: true};
-                                    if let Some(xres) = result.get_num_field("XResolution") {
-                                        res.xres = xres;
-                                    }
-                                    if let Some(mut yres) = result.get_num_field("YResolution") {
-                                        if yres == 0.0 { yres = res.xres; }
-                                        res.yres = yres;
-                                    }
-                                    if let Some(unit) = result.get_num_field("ResolutionUnit") {
-                                        res.dpi = unit as u32 == 2;
-                                        //println!("resu {:?}",res);
-                                        self.resolution = Some(res);
-                                    }
-                                    if let Some(orientation) = result.get_num_field("Orientation") {
-                                        match orientation {
-                                            6.0 => img = img.rotate90(),
-                                            3.0 => img = img.rotate180(),
-                                            8.0 => img = img.rotate270(),
-                                            _ => {}
-                                        }
-                                    }
-                                    self.exif = Some(result);
-                                }
-                            }
-                        }
-                    }
-                    else if self.image_format == SaveFormat::Jpeg {
-                        if let Ok(jpeg) = img_parts::jpeg::Jpeg::from_bytes(buffer.into()) {
-                            let raw_exif = jpeg.segments().iter()
-                                .find(|s: &&img_parts::jpeg::JpegSegment| s.marker() == 0xE1)
-                                .map(|s: &img_parts::jpeg::JpegSegment| s.contents().to_vec());
-                                
-                            if let Some(data) = raw_exif {
-                                let mut exifblock = ExifBlock::default();
-                                let len = data.len();
-                                if let Ok(result) = exifblock.open( &data, len) {
-                                    let mut res = Resolution { xres:0.0, yres:0.0, dpi: true};
-                                    if let Some(xres) = result.get_num_field("XResolution") {
-                                        res.xres = xres;
-                                    }
-                                    if let Some(mut yres) = result.get_num_field("YResolution") {
-                                        if yres == 0.0 { yres = res.xres; }
-                                        res.yres = yres;
-                                    }
-                                    if let Some(unit) = result.get_num_field("ResolutionUnit") {
-                                        res.dpi = unit as u32 == 2;
-                                        self.resolution = Some(res);
-                                    }
-                                    if let Some(orientation) = result.get_num_field("Orientation") {
-                                        match orientation {
-                                            6.0 => img = img.rotate90(),
-                                            3.0 => img = img.rotate180(),
-                                            8.0 => img = img.rotate270(),
-                                            _ => {}
-                                        }
-                                    }
-                                    //println!("{:?}",result);
-                                    self.exif = Some(result);
-                                }
-                            }
-                        }
-                    }
-                    else if self.image_format == SaveFormat::Bmp {
-                        if buffer.len() > 14 {
-                            let offset = u32::from_le_bytes(buffer[10..14].try_into().unwrap()) as usize;
-                            if offset > 54 {
-                                let potential_exif = &buffer[54..offset];
-                                if let Some(pos) = potential_exif.windows(4).position(|w| w == b"Exif" || w == b"II*" || w == b"MM*") {
-                                    let start = 54 + pos;
-                                    let mut data = buffer[start..offset].to_vec();
-                                    if !data.starts_with(b"Exif\0\0") {
-                                        let mut legacy_format = b"Exif\0\0".to_vec();
-                                        legacy_format.extend_from_slice(&data);
-                                        data = legacy_format;
-                                    }
-                                    let mut exifblock = ExifBlock::default();
-                                    let len = data.len();
-                                    if let Ok(result) = exifblock.open( &data, len) {
-                                        let mut res = Resolution { xres:0.0, yres:0.0, dpi: true};
-                                        if let Some(xres) = result.get_num_field("XResolution") {
-                                            res.xres = xres;
-                                        }
-                                        if let Some(mut yres) = result.get_num_field("YResolution") {
-                                            if yres == 0.0 { yres = res.xres; }
-                                            res.yres = yres;
-                                        }
-                                        if let Some(unit) = result.get_num_field("ResolutionUnit") {
-                                            res.dpi = unit as u32 == 2;
-                                            self.resolution = Some(res);
-                                        }
-                                        if let Some(orientation) = result.get_num_field("Orientation") {
-                                            match orientation {
-                                                6.0 => img = img.rotate90(),
-                                                3.0 => img = img.rotate180(),
-                                                8.0 => img = img.rotate270(),
-                                                _ => {}
-                                            }
-                                        }
-                                        self.exif = Some(result);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    else if self.image_format == SaveFormat::Png {
-                        if let Ok(png) = img_parts::png::Png::from_bytes(buffer.clone().into()) {
-                            if let Some(exif_chunk) = png.chunk_by_type(*b"eXIf") {
-                                let raw_content = exif_chunk.contents();
-                                let mut data = b"Exif\0\0".to_vec();
-                                data.extend_from_slice(&raw_content);
-                                let mut exifblock = ExifBlock::default();
-                                let len = data.len();
-                                if let Ok(result) = exifblock.open( &data, len) {
-                                    let mut res = Resolution { xres:0.0, yres:0.0, dpi: true};
-                                    if let Some(xres) = result.get_num_field("XResolution") {
-                                        res.xres = xres;
-                                    }
-                                    if let Some(mut yres) = result.get_num_field("YResolution") {
-                                        if yres == 0.0 { yres = res.xres; }
-                                        res.yres = yres;
-                                    }
-                                    if let Some(unit) = result.get_num_field("ResolutionUnit") {
-                                        res.dpi = unit as u32 == 2;
-                                        self.resolution = Some(res);
-                                    }
-                                    if let Some(orientation) = result.get_num_field("Orientation") {
-                                        match orientation {
-                                            6.0 => img = img.rotate90(),
-                                            3.0 => img = img.rotate180(),
-                                            8.0 => img = img.rotate270(),
-                                            _ => {}
-                                        }
-                                    }
-                                    self.exif = Some(result);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+    /// A látható RAW kép teljes dekódolása (demozaikolás) a beágyazott előnézet helyett.
+    pub fn develop_raw(&mut self) {
+        if self.image_format != SaveFormat::Raw || self.shown_image != self.image_full_path {
+            return;
+        }
+        if let Some(filepath) = self.shown_image.clone() {
+            self.start_load(filepath, true, true, 0);
+        }
+    }
 
-            self.original_image = Some(img);
-            self.resized_image = None;
-
-            // Először alaphelyzetbe állítjuk az animációs adatokat
-            self.anim_data = None;
-            self.anim_playing = false;
-            self.current_frame = 0;
-            self.is_animated = false;
-
-            // Csak GIF és WebP esetén próbáljuk meg az animációt betölteni
-            if self.image_format == SaveFormat::Gif || self.image_format == SaveFormat::Webp {
-                // Meghívjuk a segédfüggvényt (lásd lentebb)
-                self.load_animation(&filepath);
-                if self.anim_data.is_some() {
-                    self.is_animated = true;
-                    self.anim_playing = true; // Automatikus lejátszás indul
-                    self.last_frame_time = std::time::Instant::now();
-                }
+    /// A háttérszálon befejezett betöltés átvétele (az event loop-ból hívva).
+    pub fn image_loaded(&mut self) {
+        let Some(result) = self.loader.take_finished() else {
+            return;
+        };
+        self.show_loading(false);
+        match result.outcome {
+            Ok(loaded) => self.apply_loaded(result.path, result.reopen, loaded),
+            Err(e) => {
+                // az előző kép marad látható
+                self.restore_shown_image();
+                self.notify_error(&e);
             }
+        }
+    }
 
-            if (self.refit_reopen || !reopen) && self.fit_open {
-                self.want_magnify = -1.0;
-            }
-            // Cím frissítése
-            if let Some(file_name) = filepath.file_name().and_then(|n| n.to_str()) {
-                self.image_name = file_name.to_string();
-                // TODO !!!!
-                //ctx.send_viewport_cmd(egui::ViewportCommand::Title(format!( "IView - {}. {}", self.actual_index, file_name )));
+    fn apply_loaded(&mut self, filepath: PathBuf, reopen: bool, loaded: LoadedImage) {
+        let raw_preview = loaded.raw_preview;
+        self.clear_notification();
+        self.shown_image = Some(filepath.clone());
+        self.resolution = loaded.resolution;
+        self.file_meta = loaded.file_meta;
+        self.exif = loaded.exif;
+        self.set_original_image(loaded.image);
+        self.current_page = loaded.page;
+        self.page_count = loaded.page_count;
+        self.resized_image = None;
+        self.tiles = None;
+
+        // Először alaphelyzetbe állítjuk az animációs adatokat
+        self.anim_data = None;
+        self.anim_playing = false;
+        self.current_frame = 0;
+        self.anim_reverse = false;
+        self.is_animated = false;
+        self.frame_cache.clear();
+        self.timeline_thumbs.clear();
+        self.clear_onion_skin();
+
+        if let Some(anim) = loaded.anim_data {
+            // a fájlban tárolt ismétlésszám: végtelennél be van kapcsolva a Loop
+            self.anim_loop = anim.plays == 0;
+            self.anim_plays_done = 0;
+            self.anim_data = Some(anim);
+            self.is_animated = true;
+            self.anim_playing = true; // Automatikus lejátszás indul
+            self.last_frame_time = std::time::Instant::now();
+            if let Some(ui) = self.ui_handle.as_ref().and_then(|h| h.upgrade()) {
+                ui.set_loop_checked(self.anim_loop);
             }
+        }
+
+        if (self.refit_reopen || !reopen) && self.fit_open {
+            self.want_magnify = -1.0;
+        }
+        // Cím frissítése
+        if let Some(file_name) = filepath.file_name().and_then(|n| n.to_str()) {
+            self.image_name = file_name.to_string();
+        }
+
+        if self.show_timeline {
+            self.refresh_timeline();
+        }
+        let coloring = self.same_correction_open;
+        match loaded.view {
+            Some(view) => self.review_prepared(view, coloring),
+            None => self.review(coloring, false),
+        }
+        if self.show_info {
+            on_info_clicked(self, true);
+        }
+        self.schedule_prefetch();
+        if raw_preview && self.config.raw_develop {
+            self.develop_raw();
+        }
+    }
 
-            if self.show_info {
-                on_info_clicked(self, true);
+    /// A könyvtárlistában a lapozás irányába eső, majd az ellenkező oldali szomszédok előtöltése.
+    pub fn schedule_prefetch(&mut self) {
+        let count = self.config.prefetch_count;
+        let len = self.list_of_images.len();
+        if count == 0 || len < 2 {
+            self.prefetch.clear();
+            return;
+        }
+        let dir: i64 = if self.prefetch.direction < 0 { -1 } else { 1 };
+        let mut wanted: Vec<PathBuf> = Vec::new();
+        for side in [dir, -dir] {
+            for step in 1..=count as i64 {
+                let idx = (self.actual_index as i64 + side * step).rem_euclid(len as i64) as usize;
+                if idx == self.actual_index {
+                    continue;
+                }
+                let path = self.list_of_images[idx].path();
+                if !wanted.contains(&path) {
+                    wanted.push(path);
+                }
             }
-            self.review(self.same_correction_open, false);
         }
+        let limit = self.prefetch_limit_bytes();
+        let color_settings = self.load_color_settings();
+        self.prefetch.schedule(wanted, color_settings, self.max_texture_size, limit,
+            self.config.decode_limits(), self.config.anim_window_mb * 1024 * 1024);
     }
 
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    #[test]
+    fn sniff_format_recognises_magic_bytes() {
+        let cases: [(&[u8], SaveFormat); 13] = [
+            (&[0xFF, 0xD8, 0xFF, 0xE0], SaveFormat::Jpeg),
+            (b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR", SaveFormat::Png),
+            (b"GIF87a\x01\0", SaveFormat::Gif),
+            (b"GIF89a\x01\0", SaveFormat::Gif),
+            (b"II*\0\x08\0\0\0", SaveFormat::Tif),
+            (b"MM\0*\0\0\0\x08", SaveFormat::Tif),
+            (b"II*\0\x10\0\0\0CR\x02\0", SaveFormat::Raw),
+            (b"RIFF\x24\0\0\0WEBPVP8 ", SaveFormat::Webp),
+            (b"\0\0\0\x1cftypavif", SaveFormat::Avif),
+            (&[0xFF, 0x0A, 0xFA], SaveFormat::Jxl),
+            (b"\0\0\0\x0cJXL \r\n\x87\n", SaveFormat::Jxl),
+            (b"BM\x36\0\0\0", SaveFormat::Bmp),
+            (&[0, 0, 2, 0, 1, 0], SaveFormat::Ico),
+        ];
+        for (head, format) in cases {
+            assert_eq!(sniff_format(head), Some(format), "{:?}", head);
+        }
+    }
+
+    #[test]
+    fn sniff_format_rejects_unknown_and_short_input() {
+        assert_eq!(sniff_format(b""), None);
+        assert_eq!(sniff_format(b"GIF8"), None);
+        assert_eq!(sniff_format(b"RIFF\x24\0\0\0WAVEfmt "), None);
+        assert_eq!(sniff_format(b"\0\0\0\x1cftypisom"), None);
+        assert_eq!(sniff_format(b"hello world"), None);
+    }
 }
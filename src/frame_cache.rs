@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+
+use crate::colors::*;
+use crate::view_cache::*;
+
+// Animáció lejátszásakor a megjelenítésre kész (forgatott, színkorrigált) képkockák.
+// A kulcs a színbeállítás (benne a forgatás), a GPU és a kicsinyítés; ha bármelyik változik,
+// a cache kiürül. Ismételt lejátszásnál így a második körtől nincs LUT és élesítés.
+// A keret betelte után az éppen látható kockától legtávolabbiak kerülnek ki, így ugrálásnál
+// (idősáv, léptetés) mindig az aktuális kocka környéke marad meg.
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FrameKey {
+    pub colset: ColorSettings,
+    pub gpu: bool,
+    pub resized: bool,
+}
+
+#[derive(Default)]
+pub struct FrameCache {
+    key: Option<FrameKey>,
+    frames: HashMap<usize, RgbaBuffer>,
+    bytes: usize,
+    pub shown: Option<usize>, // az original_image ez a képkocka (None: nem animáció kocka)
+}
+
+impl FrameCache {
+
+    pub fn clear(&mut self) {
+        self.key = None;
+        self.frames.clear();
+        self.bytes = 0;
+    }
+
+    /// A látható képkocka kész képe, ha ugyanezekkel a beállításokkal már elkészült.
+    pub fn get(&mut self, key: &FrameKey) -> Option<RgbaBuffer> {
+        let frame = self.shown?;
+        if self.key.as_ref() != Some(key) {
+            self.clear();
+            self.key = Some(*key);
+            return None;
+        }
+        self.frames.get(&frame).cloned()
+    }
+
+    /// A látható képkocka elkészült képe; a puffer a megjelenítési cache-sel közös.
+    pub fn insert(&mut self, key: &FrameKey, rgba: &RgbaBuffer, limit_bytes: usize) {
+        let Some(frame) = self.shown else {
+            return;
+        };
+        if self.key.as_ref() != Some(key) {
+            self.clear();
+            self.key = Some(*key);
+        }
+        let size = buffer_bytes(rgba);
+        if self.frames.contains_key(&frame) || size > limit_bytes {
+            return;
+        }
+        while self.bytes + size > limit_bytes {
+            let Some(&farthest) = self.frames.keys().max_by_key(|&&i| i.abs_diff(frame)) else {
+                break;
+            };
+            if let Some(dropped) = self.frames.remove(&farthest) {
+                self.bytes -= buffer_bytes(&dropped);
+            }
+        }
+        self.bytes += size;
+        self.frames.insert(frame, rgba.clone());
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> FrameKey {
+        FrameKey { colset: ColorSettings::default(), gpu: false, resized: false }
+    }
+
+    #[test]
+    fn insert_evicts_the_frames_farthest_from_the_shown_one() {
+        let frame = RgbaBuffer::new(4, 4); // 64 bájt
+        let mut cache = FrameCache::default();
+        for i in [0, 5, 9] {
+            cache.shown = Some(i);
+            cache.insert(&key(), &frame, 3 * 64);
+        }
+        cache.shown = Some(6);
+        cache.insert(&key(), &frame, 3 * 64);
+        assert_eq!(cache.bytes(), 3 * 64);
+        let mut kept: Vec<usize> = cache.frames.keys().copied().collect();
+        kept.sort();
+        assert_eq!(kept, vec![5, 6, 9]);
+    }
+}
@@ -1,9 +1,4 @@
 use crate::colors::ColorSettings;
-//use wgpu::util::DeviceExt;
-//use std::sync::Arc;
-
-// Ez kényszeríti a Rustot, hogy figyelje a shader fájlt
-const _: &str = include_str!("shaders.wgsl");
 
 // GPU-kompatibilis ColorSettings
 #[repr(C)]
@@ -32,37 +27,69 @@ pub struct GpuFilterSettings {
     pub image_height: f32,
 }
 
-#[repr(C)]
 pub struct GpuInterface {
-    /*pub device: Arc<wgpu::Device>,
-    pub queue: Arc<wgpu::Queue>,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    pub max_texture_side: u32,
     pipe_gen_lut: wgpu::ComputePipeline,
     pipe_apply: wgpu::ComputePipeline,
-    tex_identity: wgpu::Texture,
+    _tex_identity: wgpu::Texture,
     pub tex_processed_lut: wgpu::Texture,
     params_buffer: wgpu::Buffer,
     filter_params_buffer: wgpu::Buffer,
     sampler: wgpu::Sampler,
     bind_group_gen: wgpu::BindGroup,
     bind_group_apply_0: wgpu::BindGroup,
-    bg_layout_apply: wgpu::BindGroupLayout,*/
-    colset: ColorSettings,
+    bg_layout_apply: wgpu::BindGroupLayout,
 }
 
 impl GpuInterface {
     ///////////////////////////////////////////////////////////////////////////
+    /// Önálló wgpu eszköz (nem a Slint rendererhez kötött). Ha nincs hardveres adapter,
+    /// a szoftveres (pl. lavapipe) adapterrel próbálkozik. Alapból csak Vulkan / DX12 / Metal:
+    /// a GL háttér a 3D storage textúrának csak az első rétegét írja, a LUT így fekete marad.
+    /// A WGPU_BACKEND változó ezt felülírja.
     pub fn gpu_init() -> Option<Self> {
-        None
-    }
-    /*pub fn gpu_init(render_state: &egui_wgpu::RenderState) -> Option<Self> {
-        let limits = render_state.adapter.limits();
+        let mut descriptor = wgpu::InstanceDescriptor::from_env_or_default();
+        if wgpu::Backends::from_env().is_none() {
+            descriptor.backends = wgpu::Backends::PRIMARY;
+        }
+        let instance = wgpu::Instance::new(&descriptor);
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                force_fallback_adapter: false,
+                compatible_surface: None,
+            }))
+            .or_else(|_| pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::LowPower,
+                force_fallback_adapter: true,
+                compatible_surface: None,
+            })));
+        let adapter = match adapter {
+            Ok(adapter) => adapter,
+            Err(e) => {
+                eprintln!("Nincs használható GPU adapter: {}", e);
+                return None;
+            }
+        };
+        let info = adapter.get_info();
+        let limits = adapter.limits();
         if limits.max_storage_textures_per_shader_stage < 1 {
             eprintln!("Hiba: A GPU nem támogatja a Storage Texture-öket (VirtualBox/régi driver).");
             return None;
         }
 
-        let device = render_state.device.clone();
-        let queue = render_state.queue.clone();
+        let (device, queue) = match pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            label: Some("IView Device"),
+            required_limits: limits.clone(),
+            ..Default::default()
+        })) {
+            Ok(dq) => dq,
+            Err(e) => {
+                eprintln!("GPU eszköz létrehozási hiba ({}): {}", info.name, e);
+                return None;
+            }
+        };
 
         // 3D Textúra létrehozása (33x33x33)
         let lut_desc = wgpu::TextureDescriptor {
@@ -111,15 +138,12 @@ impl GpuInterface {
 
         let filter_params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Filter Params Buffer"),
-            size: std::mem::size_of::<GpuFilterSettings>() as u64, // GpuFilterSettings a main.rs-ből vagy itt definiálva
+            size: std::mem::size_of::<GpuFilterSettings>() as u64,
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
 
-
-        // gpu_colors.rs -> gpu_init()
-
-        // --- 0. CSOPORT LAYOUT (Közös a LUT-hoz és a Képhez) ---
+        // --- 0. CSOPORT LAYOUT (LUT generálás) ---
         let bg_layout_gen = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("Layout Group 0"),
             entries: &[
@@ -144,7 +168,7 @@ impl GpuInterface {
             ],
         });
 
-        // 2. ÚJ layout az apply_effects 0-ás csoportjához (Csak a paramétereknek)
+        // Az apply_effects 0-ás csoportja (csak a paraméterek)
         let bg_layout_apply_params = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("Layout Group 0 - Apply Only"),
             entries: &[
@@ -158,7 +182,7 @@ impl GpuInterface {
             ],
         });        
 
-        // --- 1. CSOPORT LAYOUT (Képfeldolgozás) BŐVÍTÉSE ---
+        // --- 1. CSOPORT LAYOUT (Képfeldolgozás) ---
         let bg_layout_apply = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("Layout Group 1"),
             entries: &[
@@ -167,12 +191,10 @@ impl GpuInterface {
                 wgpu::BindGroupLayoutEntry { binding: 2, visibility: wgpu::ShaderStages::COMPUTE, ty: wgpu::BindingType::Texture { sample_type: wgpu::TextureSampleType::Float { filterable: true }, view_dimension: wgpu::TextureViewDimension::D3, multisampled: false }, count: None },
                 wgpu::BindGroupLayoutEntry { binding: 3, visibility: wgpu::ShaderStages::COMPUTE, ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None }, count: None },
                 wgpu::BindGroupLayoutEntry { binding: 4, visibility: wgpu::ShaderStages::COMPUTE, ty: wgpu::BindingType::StorageTexture { access: wgpu::StorageTextureAccess::WriteOnly, format: wgpu::TextureFormat::Rgba8Unorm, view_dimension: wgpu::TextureViewDimension::D2 }, count: None },
-                // EZ HIÁNYZOTT: A colset_apply binding (5-ös)
                 wgpu::BindGroupLayoutEntry { binding: 5, visibility: wgpu::ShaderStages::COMPUTE, ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None }, count: None },
             ],
         });
 
-
         // Layout a LUT generálóhoz (csak 0-ás csoport)
         let layout_gen_lut = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Layout Gen LUT"),
@@ -187,10 +209,9 @@ impl GpuInterface {
             push_constant_ranges: &[],
         });
 
-
         let pipe_gen_lut = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
             label: Some("Gen LUT Pipeline"),
-            layout: Some(&layout_gen_lut), // <--- EZT ÍRD BE
+            layout: Some(&layout_gen_lut),
             module: &shader,
             entry_point: Some("generate_lut"),
             compilation_options: Default::default(),
@@ -199,14 +220,13 @@ impl GpuInterface {
 
         let pipe_apply = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
             label: Some("Apply Effects Pipeline"),
-            layout: Some(&layout_apply_img), // <--- EZT ÍRD BE
+            layout: Some(&layout_apply_img),
             module: &shader,
             entry_point: Some("apply_effects"),
             compilation_options: Default::default(),
             cache: None,
         });
 
-        // Helyes BindGroup létrehozás
         let bind_group_gen = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Bind Group Gen"),
             layout: &bg_layout_gen,
@@ -219,38 +239,34 @@ impl GpuInterface {
 
         let bind_group_apply_0 = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Bind Group Apply 0 (Truly Params Only)"),
-            layout: &bg_layout_apply_params, // Az új, szűkített layout
+            layout: &bg_layout_apply_params,
             entries: &[
                 wgpu::BindGroupEntry { binding: 0, resource: params_buffer.as_entire_binding() },
             ],
         });
 
-        let bg_layout_apply = pipe_apply.get_bind_group_layout(1);
-
-        // Pipeline-ok és BindGroup-ok létrehozása...
-        // (Ha bármi hibázik, return None)
-        Some(Self {
-            device: device.into(),
-            queue: queue.into(),
+        let interface = Self {
+            max_texture_side: device.limits().max_texture_dimension_2d,
+            device,
+            queue,
             pipe_gen_lut,
             pipe_apply,
-            tex_identity,
+            _tex_identity: tex_identity,
             tex_processed_lut,
             params_buffer,
             filter_params_buffer,
             sampler,
-            bind_group_gen, // Ezt is hozzá kell adni a struct-hoz!
+            bind_group_gen,
             bind_group_apply_0,
-            bg_layout_apply, // Későbbi kép-bindinghoz
-            colset: ColorSettings::default(),
-        })
-    }*/
+            bg_layout_apply,
+        };
+
+        Some(interface)
+    }
     
     ///////////////////////////////////////////////////////////////////////////
     /// Frissíti a GPU-n lévő 3D LUT-ot a megadott színbeállítások alapján.
-     pub fn change_colorcorrection(&self, colset: &ColorSettings, width: f32, height: f32) {
-     }
-     /*
+    pub fn change_colorcorrection(&self, colset: &ColorSettings, width: f32, height: f32) {
         let gpu_settings = GpuColorSettings {
             setted: if colset.is_setted() { 1 } else { 0 },
             gamma: colset.gamma,
@@ -284,21 +300,21 @@ impl GpuInterface {
                 label: Some("LUT Compute Pass"),
                 timestamp_writes: None,
             });
-
             cpass.set_pipeline(&self.pipe_gen_lut);
-
             cpass.set_bind_group(0, &self.bind_group_gen, &[]);
-
             cpass.dispatch_workgroups(9, 9, 9); // 33/4 = 9 (felfelé kerekítve)
         }
 
         self.queue.submit(Some(encoder.finish()));
-    }*/
-    ///////////////////////////////////////////////////////////////////////////
-
-    pub fn generate_image(&self, img_data: &mut [u8], width: u32, height: u32) {
     }
-/*
+
+    ///////////////////////////////////////////////////////////////////////////
+    /// A kép feldolgozása a GPU-n (élesítés + LUT). `false`, ha a kép túl nagy az eszköznek
+    /// vagy a visszaolvasás nem sikerült; ilyenkor a CPU-s feldolgozás kell.
+    pub fn generate_image(&self, img_data: &mut [u8], width: u32, height: u32) -> bool {
+        if width == 0 || height == 0 || width > self.max_texture_side || height > self.max_texture_side {
+            return false;
+        }
         let size = wgpu::Extent3d { width, height, depth_or_array_layers: 1 };
 
         // 1. Forrás kép feltöltése
@@ -332,7 +348,7 @@ impl GpuInterface {
             view_formats: &[],
         });
 
-        // 4. Bind Group létrehozása a képfeldolgozáshoz
+        // 3. Bind Group létrehozása a képfeldolgozáshoz
         let bind_group_apply = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Apply Bind Group"),
             layout: &self.bg_layout_apply,
@@ -340,7 +356,7 @@ impl GpuInterface {
                 wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&tex_src.create_view(&Default::default())) },
                 wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&self.sampler) },
                 wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(&self.tex_processed_lut.create_view(&Default::default())) },
-                wgpu::BindGroupEntry { binding: 3, resource: self.filter_params_buffer.as_entire_binding() }, // Feltételezve, hogy van ilyen buffer az initben
+                wgpu::BindGroupEntry { binding: 3, resource: self.filter_params_buffer.as_entire_binding() },
                 wgpu::BindGroupEntry { binding: 4, resource: wgpu::BindingResource::TextureView(&tex_out.create_view(&Default::default())) },
                 wgpu::BindGroupEntry { binding: 5, resource: self.params_buffer.as_entire_binding() }, 
             ],
@@ -350,15 +366,10 @@ impl GpuInterface {
         {
             let mut cpass = encoder.begin_compute_pass(&Default::default());
             cpass.set_pipeline(&self.pipe_apply);
-            
-            // Most már be van állítva a 0-ás index, de olyan BindGroup-pal, 
-            // ami nem okoz "conflicting usage" hibát (nincs STORAGE_READ_WRITE benne)
             cpass.set_bind_group(0, &self.bind_group_apply_0, &[]); 
-            
             cpass.set_bind_group(1, &bind_group_apply, &[]);
-            
-            let workgroup_x = (width + 15) / 16;
-            let workgroup_y = (height + 15) / 16;
+            let workgroup_x = width.div_ceil(16);
+            let workgroup_y = height.div_ceil(16);
             cpass.dispatch_workgroups(workgroup_x, workgroup_y, 1);
         }
 
@@ -368,7 +379,7 @@ impl GpuInterface {
 
         let staging_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Staging Buffer"),
-            size: (padded_bytes_per_row * height) as u64, // A padded méretet használjuk
+            size: (padded_bytes_per_row * height) as u64,
             usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
             mapped_at_creation: false,
         });
@@ -379,7 +390,7 @@ impl GpuInterface {
                 buffer: &staging_buffer,
                 layout: wgpu::TexelCopyBufferLayout  { 
                     offset: 0, 
-                    bytes_per_row: Some(padded_bytes_per_row), // ITT A JAVÍTÁS
+                    bytes_per_row: Some(padded_bytes_per_row),
                     rows_per_image: Some(height) },
             },
             size,
@@ -387,38 +398,34 @@ impl GpuInterface {
 
         self.queue.submit(Some(encoder.finish()));
 
-        // 6. Letöltés a CPU-ra
+        // 4. Letöltés a CPU-ra
         let buffer_slice = staging_buffer.slice(..);
         let (sender, receiver) = std::sync::mpsc::channel();
-        buffer_slice.map_async(wgpu::MapMode::Read, move |v| sender.send(v).unwrap());
+        buffer_slice.map_async(wgpu::MapMode::Read, move |v| { let _ = sender.send(v); });
 
-        let _ = self.device.poll(wgpu::PollType::wait_indefinitely());
+        if let Err(e) = self.device.poll(wgpu::PollType::wait_indefinitely()) {
+            eprintln!("GPU poll hiba: {}", e);
+            return false;
+        }
 
-        if let Ok(Ok(())) = receiver.recv() {
+        let Ok(Ok(())) = receiver.recv() else {
+            return false;
+        };
+        {
             let data = buffer_slice.get_mapped_range();
-            
-            // 1. Számoljuk ki a sorok hosszát
-            let width_bytes = width as usize * 4;
-            // A padded_bytes_per_row-t ugyanúgy számold, mint a buffer létrehozásakor!
-            let align = 256;
-            let padded_bytes_per_row = (width_bytes + align - 1) & !(align - 1);
-
-            // 2. Soronkénti másolás (ez a lényeg!)
+            let width_bytes = width_bytes as usize;
+            let padded_bytes_per_row = padded_bytes_per_row as usize;
+            // Soronkénti másolás, a sorvégi kiegészítés nélkül
             for y in 0..height as usize {
                 let gpu_start = y * padded_bytes_per_row;
-                let gpu_end = gpu_start + width_bytes;
-                
                 let cpu_start = y * width_bytes;
-                let cpu_end = cpu_start + width_bytes;
-                
-                // Csak a hasznos pixeladatokat másoljuk át a kiegészítés nélkül
-                img_data[cpu_start..cpu_end].copy_from_slice(&data[gpu_start..gpu_end]);
+                img_data[cpu_start..cpu_start + width_bytes].copy_from_slice(&data[gpu_start..gpu_start + width_bytes]);
             }
-
-            drop(data);
-            staging_buffer.unmap();
         }
-    }    */
+        staging_buffer.unmap();
+        true
+    }
+
 
 }
     ///////////////////////////////////////////////////////////////////////////
@@ -445,3 +452,81 @@ fn create_3d_identity_data() -> Vec<u8> {
 }
 
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colors::Lut4ColorSettings;
+
+    /// A GPU és a CPU (Lut4ColorSettings::apply_lut) eredményének megengedett eltérése csatornánként.
+    /// A két út közti különbség forrásai: a CPU lefelé kerekíti a LUT rácspontjait és az élesített
+    /// értéket, a GPU a legközelebbire; a textúra szűrés súlyait a Vulkan csak 4 bitre garantálja.
+    /// Ezeket a shader lebegőpontos modelljébe építve a teszt beállításain legfeljebb 6, átlagban
+    /// 0.5 alatti az eltérés; a határ ennek kis ráhagyással felfelé kerekítve.
+    const GPU_MAX_DIFF: u8 = 8;
+    const GPU_MEAN_DIFF: f32 = 1.0;
+
+    /// Egy szintetikus tesztképen összeveti a GPU kimenetét a CPU-s `Lut4ColorSettings::apply_lut`
+    /// eredményével. Adapter kell hozzá (szoftveres is, pl. WGPU_ADAPTER_NAME=lavapipe), nélküle
+    /// hibát ad, ezért csak kérésre fut: cargo test -- --ignored
+    #[test]
+    #[ignore = "needs a GPU adapter: cargo test -- --ignored"]
+    fn gpu_matches_cpu_lut() {
+        let interface = GpuInterface::gpu_init().expect("no usable GPU adapter");
+        let (width, height) = (96u32, 64u32);
+        let test_image = image::RgbaImage::from_fn(width, height, |x, y| {
+            image::Rgba([
+                (x * 255 / (width - 1)) as u8,
+                (y * 255 / (height - 1)) as u8,
+                ((x + y) * 4 % 256) as u8,
+                255,
+            ])
+        });
+
+        let color_only = ColorSettings {
+            gamma: 1.3,
+            contrast: 0.2,
+            brightness: 0.05,
+            hue_shift: 30.0,
+            saturation: 0.3,
+            ..ColorSettings::default()
+        };
+        let oklab = ColorSettings {
+            oklab: true,
+            ..color_only
+        };
+        // élesítés csak HSV-vel: az Oklab LUT a színárnyalat körbefordulásánál ugrik, ott a kerekítés
+        // egyetlen szintnyi különbsége is több tucat szintté nőhet
+        let sharpened = ColorSettings {
+            sharpen_amount: 1.0,
+            sharpen_radius: 1.0,
+            ..color_only
+        };
+
+        for colset in [color_only, oklab, sharpened] {
+            let mut max_diff = 0u8;
+            let mut sum_diff = 0u64;
+            let mut count = 0u64;
+            let mut cpu_image = test_image.clone();
+            let mut lut = Lut4ColorSettings::default();
+            lut.update_lut(&colset);
+            lut.apply_lut(&mut cpu_image);
+
+            let mut gpu_image = test_image.clone();
+            interface.change_colorcorrection(&colset, width as f32, height as f32);
+            assert!(interface.generate_image(gpu_image.as_mut(), width, height), "GPU processing did not run");
+
+            for (c, g) in cpu_image.pixels().zip(gpu_image.pixels()) {
+                for i in 0..3 { // az alfa csatornát a shader mindig 1.0-ra írja
+                    let diff = c[i].abs_diff(g[i]);
+                    max_diff = max_diff.max(diff);
+                    sum_diff += diff as u64;
+                    count += 1;
+                }
+            }
+            let mean_diff = sum_diff as f32 / count as f32;
+            assert!(max_diff <= GPU_MAX_DIFF && mean_diff <= GPU_MEAN_DIFF,
+                "{:?}: max diff {} (limit {}), mean diff {:.2} (limit {})",
+                colset, max_diff, GPU_MAX_DIFF, mean_diff, GPU_MEAN_DIFF);
+        }
+    }
+}
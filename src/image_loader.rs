@@ -0,0 +1,389 @@
+use std::fs;
+use std::io::{Read, Seek};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use img_parts::ImageEXIF;
+
+use crate::MainWindow;
+use crate::exif_my::*;
+use crate::colors::*;
+use crate::file_handlers::SaveFormat;
+use crate::image_processing::*;
+use crate::errors::*;
+use crate::modern_formats::decode_jxl;
+use crate::raw_image::decode_raw;
+use crate::pages::{decode_page, page_count};
+use crate::anim_stream::{read_timing, AnimTiming, FrameSource, FrameStream};
+
+// Háttérszálas képbetöltés: dekódolás, EXIF, animáció és az első megjelenítés
+// előkészítése a UI szálon kívül fut, az eredményt az event loop kapja meg.
+
+/// Minden, amit a worker szál előállít, és a UI szálon csak át kell venni.
+pub struct LoadedImage {
+    pub image: Arc<image::DynamicImage>,
+    pub file_meta: Option<fs::Metadata>,
+    pub exif: Option<ExifBlock>,
+    pub resolution: Option<Resolution>,
+    pub anim_data: Option<AnimatedImage>,
+    pub view: Option<PreparedView>,
+    pub raw_preview: bool, // RAW fájl beágyazott JPEG előnézete, nem a demozaikolt kép
+    pub page: usize,       // többoldalas fájlnál a betöltött oldal
+    pub page_count: usize,
+}
+
+pub struct LoadRequest {
+    pub path: PathBuf,
+    pub format: SaveFormat,
+    pub reopen: bool,
+    pub color_settings: ColorSettings, // ezzel készül az első megjelenítés
+    pub max_texture_size: u32,
+    pub limits: image::Limits, // méret és foglalás korlát a dekódoláshoz (AppSettings)
+    pub raw_develop: bool,     // RAW fájlnál előnézet helyett teljes dekódolás
+    pub page: usize,           // TIFF oldal / ICO kép sorszáma
+    pub anim_window_bytes: usize, // animációnál a dekódolt képkockák ablakának memóriája
+}
+
+pub struct LoadResult {
+    pub generation: u64,
+    pub path: PathBuf,
+    pub reopen: bool,
+    pub outcome: ViewerResult<LoadedImage>,
+}
+
+/// Egy betöltéshez tartozó megszakítás jelző: ha közben újabb betöltés indult, érvénytelen.
+#[derive(Clone)]
+pub struct CancelToken {
+    generation: Arc<AtomicU64>,
+    mine: u64,
+}
+
+impl CancelToken {
+    pub fn new(generation: Arc<AtomicU64>, mine: u64) -> Self {
+        Self { generation, mine }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.generation.load(Ordering::SeqCst) != self.mine
+    }
+}
+
+pub struct ImageLoader {
+    generation: Arc<AtomicU64>,
+    sender: Sender<LoadResult>,
+    receiver: Receiver<LoadResult>,
+    pub loading: bool,
+}
+
+impl Default for ImageLoader {
+    fn default() -> Self {
+        let (sender, receiver) = channel();
+        Self {
+            generation: Arc::new(AtomicU64::new(0)),
+            sender,
+            receiver,
+            loading: false,
+        }
+    }
+}
+
+impl ImageLoader {
+
+    /// Elindít egy új betöltést; a még futó előzőt ezzel egyben meg is szakítja.
+    pub fn start(&mut self, request: LoadRequest, ui_handle: Option<slint::Weak<MainWindow>>) -> u64 {
+        let mine = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let token = CancelToken::new(self.generation.clone(), mine);
+        let sender = self.sender.clone();
+        self.loading = true;
+        std::thread::spawn(move || {
+            let outcome = decode_guarded(&request, &token);
+            if token.is_cancelled() {
+                return;
+            }
+            let _ = sender.send(LoadResult {
+                generation: mine,
+                path: request.path,
+                reopen: request.reopen,
+                outcome,
+            });
+            let _ = slint::invoke_from_event_loop(move || {
+                if let Some(ui) = ui_handle.and_then(|h| h.upgrade()) {
+                    ui.invoke_image_loaded();
+                }
+            });
+        });
+        mine
+    }
+
+    pub fn cancel(&mut self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.loading = false;
+    }
+
+    /// Az utoljára indított betöltés eredménye, ha már elkészült. A régiek eldobódnak.
+    pub fn take_finished(&mut self) -> Option<LoadResult> {
+        let current = self.generation.load(Ordering::SeqCst);
+        let mut found = None;
+        while let Ok(result) = self.receiver.try_recv() {
+            if result.generation == current {
+                found = Some(result);
+            }
+        }
+        if found.is_some() {
+            self.loading = false;
+        }
+        found
+    }
+}
+
+pub fn decode_image_file(request: &LoadRequest, token: &CancelToken) -> ViewerResult<LoadedImage> {
+    let cancelled = || ViewerError::load(&request.path, "cancelled");
+    let mut raw_preview = false;
+    let page_count = page_count(&request.path, request.format);
+    let page = request.page.min(page_count - 1);
+    // az ICO/CUR mindig a saját (méret szerint rendezett) listájából töltődik
+    let paged = request.format == SaveFormat::Ico || (request.format == SaveFormat::Tif && page_count > 1);
+    let mut img = match request.format {
+        _ if paged => decode_page(&request.path, request.format, page, &request.limits)
+            .map_err(|e| ViewerError::load(&request.path, e))?,
+        // a JPEG XL-t az image crate nem ismeri, a jxl-oxide dekódere olvassa
+        SaveFormat::Jxl => decode_jxl(&request.path, request.limits.clone())
+            .map_err(|e| ViewerError::load(&request.path, e))?,
+        SaveFormat::Raw => {
+            let picture = decode_raw(&request.path, request.raw_develop, &request.limits)
+                .map_err(|e| ViewerError::load(&request.path, e))?;
+            raw_preview = picture.preview;
+            picture.image
+        }
+        _ => {
+            // a formátumot a tartalom dönti el, nem a kiterjesztés
+            let mut reader = image::ImageReader::open(&request.path)
+                .and_then(|r| r.with_guessed_format())
+                .map_err(|e| ViewerError::load(&request.path, e))?;
+            reader.limits(request.limits.clone());
+            reader.decode()
+                .map_err(|e| ViewerError::load(&request.path, decode_error_text(&e, &request.limits)))?
+        }
+    };
+    if token.is_cancelled() {
+        return Err(cancelled());
+    }
+    let file_meta = fs::metadata(&request.path).ok(); // for file size & date
+    let mut resolution = read_resolution(&request.path, request.format);
+    let exif = read_exif(&request.path, request.format, &mut img, &mut resolution);
+    if token.is_cancelled() {
+        return Err(cancelled());
+    }
+
+    // Csak GIF, WebP és (APNG-t kereső) PNG esetén próbáljuk meg az animációt betölteni
+    let anim_data = if matches!(request.format, SaveFormat::Gif | SaveFormat::Webp | SaveFormat::Png) {
+        load_animation(request, token)
+    } else {
+        None
+    };
+    if token.is_cancelled() {
+        return Err(cancelled());
+    }
+
+    // animációnál az első képkocka a megjelenített kép is, a memória közös
+    let (img, anim_data) = match anim_data.as_ref().and_then(|anim| anim.frames.wait_frame(0)) {
+        Some(first) => (first, anim_data),
+        None => (Arc::new(img), None),
+    };
+    let view = Some(prepare_view(&img, &request.color_settings, request.max_texture_size));
+    Ok(LoadedImage { image: img, file_meta, exif, resolution, anim_data, view, raw_preview, page, page_count })
+}
+
+/// A dekóderekben keletkező pánik ne vigye el a betöltő szálat: hibaként jelenik meg.
+pub fn decode_guarded(request: &LoadRequest, token: &CancelToken) -> ViewerResult<LoadedImage> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| decode_image_file(request, token)))
+        .unwrap_or_else(|_| Err(ViewerError::load(&request.path, "The file is damaged, the decoder stopped on it")))
+}
+
+/// Érthető hibaüzenet; a korlát túllépésénél a beállított határokat is megnevezi.
+pub fn decode_error_text(e: &image::ImageError, limits: &image::Limits) -> String {
+    match e {
+        image::ImageError::Limits(_) => format!(
+            "The image exceeds the configured limits (max {} x {} pixel, {} MB)",
+            limits.max_image_width.unwrap_or(0),
+            limits.max_image_height.unwrap_or(0),
+            limits.max_alloc.unwrap_or(0) / (1024 * 1024)),
+        _ => e.to_string(),
+    }
+}
+
+/// Az animáció időzítése (első, könnyű menet) és a képkockák háttérszálas dekódolása.
+pub fn load_animation(request: &LoadRequest, token: &CancelToken) -> Option<AnimatedImage> {
+    let AnimTiming { delays, plays } = read_timing(&request.path, request.format)?;
+    if delays.len() <= 1 || token.is_cancelled() {
+        return None;
+    }
+    let source = FrameSource {
+        path: request.path.clone(),
+        format: request.format,
+        limits: request.limits.clone(),
+    };
+    let frames = FrameStream::start(source, delays.len(), request.anim_window_bytes);
+    Some(AnimatedImage::new(frames, delays, plays))
+}
+
+pub fn read_resolution(filepath: &PathBuf, format: SaveFormat) -> Option<Resolution> {
+    match format {
+        SaveFormat::Tif => {
+            let file = std::fs::File::open(filepath).ok()?;
+            let mut decoder = tiff::decoder::Decoder::new(file).ok()?;
+            if let Ok(tiff::decoder::ifd::Value::Rational(n, d)) =
+                decoder.get_tag(tiff::tags::Tag::XResolution)
+            {
+                let xres = n as f32 / d as f32;
+                if let Ok(tiff::decoder::ifd::Value::Rational(n, d)) =
+                    decoder.get_tag(tiff::tags::Tag::YResolution)
+                {
+                    let yres = n as f32 / d as f32;
+                    if let Ok(unit) = decoder.get_tag(tiff::tags::Tag::ResolutionUnit) {
+                        let dpi = unit == tiff::decoder::ifd::Value::Unsigned(2);
+                        return Some(Resolution { xres, yres, dpi });
+                    }
+                }
+            }
+            None
+        }
+        SaveFormat::Bmp => {
+            let mut file = std::fs::File::open(filepath).ok()?;
+            let mut buffer = [0u8; 8];
+            if file.seek(std::io::SeekFrom::Start(38)).is_ok()
+                && file.read_exact(&mut buffer).is_ok()
+            {
+                let x_ppm = u32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
+                let y_ppm = u32::from_le_bytes([buffer[4], buffer[5], buffer[6], buffer[7]]);
+                if x_ppm > 0 && y_ppm > 0 {
+                    let xres = (x_ppm as f32 / 39.3701).round();
+                    let yres = (y_ppm as f32 / 39.3701).round();
+                    return Some(Resolution { xres, yres, dpi: true });
+                }
+            }
+            None
+        }
+        SaveFormat::Png => {
+            let file = std::fs::File::open(filepath).ok()?;
+            let reader = std::io::BufReader::new(file);
+            let decoder = png::Decoder::new(reader);
+            let reader = decoder.read_info().ok()?;
+            let phys = reader.info().pixel_dims?;
+            if phys.unit == png::Unit::Meter {
+                let xres = (phys.xppu as f32 / 39.3701).round();
+                let yres = (phys.yppu as f32 / 39.3701).round();
+                return Some(Resolution { xres, yres, dpi: true });
+            }
+            None
+        }
+        SaveFormat::Jpeg => {
+            let mut file = std::fs::File::open(filepath).ok()?;
+            let mut header = [0u8; 18];
+            file.read_exact(&mut header).ok()?;
+            // Ellenőrizzük a JFIF mágiát: [FF D8 FF E0 ... 'J' 'F' 'I' 'F']
+            if header[0..4] == [0xFF, 0xD8, 0xFF, 0xE0] && &header[6..10] == b"JFIF" {
+                let unit = header[13]; // 1 = DPI (dots per inch), 2 = DPC (dots per cm)
+                let xres = u16::from_be_bytes([header[14], header[15]]) as f32;
+                let yres = u16::from_be_bytes([header[16], header[17]]) as f32;
+                if xres > 0.0 && yres > 0.0 && (unit == 1 || unit == 2) {
+                    return Some(Resolution { xres, yres, dpi: unit == 1 });
+                }
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Kiolvassa az EXIF blokkot; a felbontást felülírja, a képet az Orientation szerint elforgatja.
+pub fn read_exif(filepath: &PathBuf, format: SaveFormat, img: &mut image::DynamicImage,
+        resolution: &mut Option<Resolution>) -> Option<ExifBlock> {
+    let mut f = std::fs::File::open(filepath).ok()?;
+    let mut buffer = Vec::new();
+    f.read_to_end(&mut buffer).ok()?;
+    match format {
+        SaveFormat::Webp => {
+            let webp = img_parts::webp::WebP::from_bytes(buffer.into()).ok()?;
+            let exif_bytes = webp.exif()?;
+            let mut data = exif_bytes.to_vec();
+            if !data.starts_with(b"Exif\0\0") {
+                let mut legacy_format = b"Exif\0\0".to_vec();
+                legacy_format.extend_from_slice(&data);
+                data = legacy_format;
+            }
+            exif_from_data(&data, img, resolution)
+        }
+        SaveFormat::Jpeg => {
+            let jpeg = img_parts::jpeg::Jpeg::from_bytes(buffer.into()).ok()?;
+            let data = jpeg.segments().iter()
+                .find(|s: &&img_parts::jpeg::JpegSegment| s.marker() == 0xE1)
+                .map(|s: &img_parts::jpeg::JpegSegment| s.contents().to_vec())?;
+            exif_from_data(&data, img, resolution)
+        }
+        SaveFormat::Bmp => {
+            if buffer.len() <= 14 {
+                return None;
+            }
+            let offset = u32::from_le_bytes(buffer.get(10..14)?.try_into().ok()?) as usize;
+            if offset <= 54 {
+                return None;
+            }
+            let offset = offset.min(buffer.len()); // csonka fájl: a pixel adat kezdete a fájl vége után
+            let potential_exif = buffer.get(54..offset)?;
+            let pos = potential_exif.windows(4).position(|w| w == b"Exif" || w == b"II*\0" || w == b"MM\0*")?;
+            let start = 54 + pos;
+            let mut data = buffer[start..offset].to_vec();
+            if !data.starts_with(b"Exif\0\0") {
+                let mut legacy_format = b"Exif\0\0".to_vec();
+                legacy_format.extend_from_slice(&data);
+                data = legacy_format;
+            }
+            exif_from_data(&data, img, resolution)
+        }
+        SaveFormat::Png => {
+            let png = img_parts::png::Png::from_bytes(buffer.into()).ok()?;
+            let exif_chunk = png.chunk_by_type(*b"eXIf")?;
+            let mut data = b"Exif\0\0".to_vec();
+            data.extend_from_slice(exif_chunk.contents());
+            exif_from_data(&data, img, resolution)
+        }
+        SaveFormat::Raw => {
+            // a RAW maga egy TIFF: a pixel adatok előtti metaadat rész marad meg
+            let mut data = b"Exif\0\0".to_vec();
+            data.extend_from_slice(&buffer);
+            let mut block = exif_from_data(&data, img, resolution)?;
+            block.raw_exif.truncate(block.lastexifrefd.max(14));
+            block.raw_exif_length = block.raw_exif.len();
+            Some(block)
+        }
+        _ => None,
+    }
+}
+
+fn exif_from_data(data: &[u8], img: &mut image::DynamicImage, resolution: &mut Option<Resolution>) -> Option<ExifBlock> {
+    let mut exifblock = ExifBlock::default();
+    let result = exifblock.open(data, data.len()).ok()?;
+    let mut res = Resolution { xres: 0.0, yres: 0.0, dpi: true };
+    if let Some(xres) = result.get_num_field("XResolution") {
+        res.xres = xres;
+    }
+    if let Some(mut yres) = result.get_num_field("YResolution") {
+        if yres == 0.0 { yres = res.xres; }
+        res.yres = yres;
+    }
+    if let Some(unit) = result.get_num_field("ResolutionUnit") {
+        res.dpi = unit as u32 == 2;
+        *resolution = Some(res);
+    }
+    if let Some(orientation) = result.get_num_field("Orientation") {
+        match orientation {
+            6.0 => *img = img.rotate90(),
+            3.0 => *img = img.rotate180(),
+            8.0 => *img = img.rotate270(),
+            _ => {}
+        }
+    }
+    Some(result)
+}
@@ -2,31 +2,35 @@ use serde::{Deserialize, Serialize};
 use arboard::Clipboard;
 use std::path::PathBuf;
 use std::env;
+use std::sync::Arc;
 use crate::ImageViewer;
 use crate::colors::*;
-use slint::{Color, Image, SharedPixelBuffer, Rgba8Pixel, ComponentHandle};
+use slint::{Color, Image, SharedPixelBuffer, Rgba8Pixel, ComponentHandle, ModelRc, VecModel};
 use crate::Pf32;
 //use image::math::Rect;
 use crate::ImageState;
+use crate::TileSlint;
 use crate::file_callbacks::set_animation;
+use crate::tiles::*;
+use crate::view_cache::*;
+use crate::frame_cache::FrameKey;
+use crate::errors::*;
+use crate::anim_stream::FrameStream;
+use crate::file_handlers::detect_format;
 
 // Segédfüggvény a vágólapon lévő kép kimentéséhez egy ideiglenes fájlba
-pub fn save_clipboard_image() -> Option<PathBuf> {
-    let mut clipboard = Clipboard::new().ok()?;
-    if let Ok(image_data) = clipboard.get_image() {
-        let temp_path = env::temp_dir().join("rust_image_viewer_clipboard.png");
-        // Konvertálás arboard formátumból image formátumba
-        if let Some(buf) = image::ImageBuffer::<image::Rgba<u8>, std::vec::Vec<u8>>::from_raw(
-            image_data.width as u32,
-            image_data.height as u32,
-            image_data.bytes.into_owned(),
-        ) {
-            if buf.save(&temp_path).is_ok() {
-                return Some(temp_path);
-            }
-        }
-    }
-    None
+pub fn save_clipboard_image() -> ViewerResult<PathBuf> {
+    let mut clipboard = Clipboard::new().map_err(ViewerError::clipboard)?;
+    let image_data = clipboard.get_image().map_err(ViewerError::clipboard)?;
+    let temp_path = env::temp_dir().join("rust_image_viewer_clipboard.png");
+    // Konvertálás arboard formátumból image formátumba
+    let buf = image::ImageBuffer::<image::Rgba<u8>, std::vec::Vec<u8>>::from_raw(
+        image_data.width as u32,
+        image_data.height as u32,
+        image_data.bytes.into_owned(),
+    ).ok_or_else(|| ViewerError::clipboard("The clipboard image has an invalid size"))?;
+    buf.save(&temp_path).map_err(|e| ViewerError::save(&temp_path, e))?;
+    Ok(temp_path)
 }
 
 
@@ -88,97 +92,455 @@ pub struct Resolution {
 #[derive(Clone)]
 pub struct AnimatedImage {
     //pub anim_frames: Vec<egui::TextureHandle>, // GPU textúrák // old
-    pub anim_frames: Vec<image::DynamicImage>,
-    pub delays: Vec<std::time::Duration>, // Időzítések
+    pub frames: FrameStream, // csúszó ablak; az aktuális képkocka az original_image-dzsel közös
+    pub order: Vec<usize>, // a lejátszott kockák sorszáma a fájlban (szerkesztés után eltérhet)
+    pub delays: Vec<std::time::Duration>, // Időzítések, a lejátszott kockákhoz
     pub total_frames: usize,
+    pub plays: u32, // a fájlban tárolt lejátszásszám, 0: végtelen
+}
+
+impl AnimatedImage {
+    pub fn new(frames: FrameStream, delays: Vec<std::time::Duration>, plays: u32) -> Self {
+        let total_frames = delays.len();
+        Self { frames, order: (0..total_frames).collect(), delays, total_frames, plays }
+    }
+
+    /// A képkockák memóriája; a `shared` képet (pl. az original_image-et) nem számolja kétszer.
+    pub fn bytes(&self, shared: Option<&Arc<image::DynamicImage>>) -> usize {
+        self.frames.bytes(shared)
+    }
+
+    /// A lejátszott sorrend i. kockájának sorszáma a fájlban.
+    pub fn source_index(&self, frame: usize) -> Option<usize> {
+        self.order.get(frame).copied()
+    }
+
+    /// A lejátszott sorrend i. kockája, ha a háttérszál már elkészítette.
+    pub fn frame(&self, frame: usize) -> Option<Arc<image::DynamicImage>> {
+        self.frames.frame(self.source_index(frame)?)
+    }
+
+    /// Minden lejátszott képkocka a (szerkesztett) sorrendben, a fájlból újra dekódolva;
+    /// a memóriában csak a sorrendben később újra szereplő kockák várakoznak.
+    pub fn for_each_frame(&self, f: impl FnMut(usize, &image::DynamicImage) -> Result<(), String>) -> Result<(), String> {
+        self.frames.source().for_each_in_order(&self.order, f)
+    }
+}
+
+/// Ennyi másodpercig látszik egy hibaértesítés.
+pub const NOTIFY_SECONDS: u64 = 8;
+
+/// Háttérszálról az értesítési sávba (haladás, eredmény); `done` után a szöveg a többi
+/// értesítéshez hasonlóan NOTIFY_SECONDS múlva eltűnik, ha közben nem jött újabb.
+pub fn worker_status(weak: &slint::Weak<crate::MainWindow>, text: String, done: bool) {
+    let weak = weak.clone();
+    let _ = slint::invoke_from_event_loop(move || {
+        if let Some(ui) = weak.upgrade() {
+            ui.set_notification(text.clone().into());
+            if done {
+                slint::Timer::single_shot(std::time::Duration::from_secs(NOTIFY_SECONDS), move || {
+                    if let Some(ui) = weak.upgrade().filter(|ui| ui.get_notification() == text.as_str()) {
+                        ui.set_notification("".into());
+                    }
+                });
+            }
+        }
+    });
+}
+
+/// A textúra méret felső határa, amíg a renderer valódi értéke nem ismert (és szoftveres renderelésnél);
+/// e fölött kicsinyített kép és csempék jelennek meg.
+pub const MAX_TEXTURE_SIZE: u32 = 4096;
+
+/// A GL_MAX_TEXTURE_SIZE lekérdezése a femtovg renderer OpenGL környezetéből.
+pub fn query_gl_max_texture_size(get_proc_address: &dyn Fn(&std::ffi::CStr) -> *const std::ffi::c_void) -> Option<u32> {
+    const GL_MAX_TEXTURE_SIZE: u32 = 0x0D33;
+    let ptr = get_proc_address(c"glGetIntegerv");
+    if ptr.is_null() {
+        return None;
+    }
+    let gl_get_integerv: extern "system" fn(u32, *mut i32) = unsafe { std::mem::transmute(ptr) };
+    let mut value: i32 = 0;
+    gl_get_integerv(GL_MAX_TEXTURE_SIZE, &mut value);
+    if value > 0 { Some(value as u32) } else { None }
+}
+
+/// A háttérszálon előkészített, megjelenítésre kész kép.
+pub struct PreparedView {
+    pub color_settings: ColorSettings,
+    pub resized_image: Option<image::DynamicImage>,
+    pub resize: f32,
+    pub rgba_image: RgbaBuffer,
+    pub pyramid: Option<TilePyramid>,
+}
+
+/// Ha a kép nagyobb a megengedett textúra méretnél, kicsinyített változatot és a kicsinyítés arányát adja.
+pub fn fit_to_texture(image: &image::DynamicImage, max_size: u32) -> Option<(image::DynamicImage, f32)> {
+    let w_orig = image.width();
+    let h_orig = image.height();
+    if w_orig > max_size || h_orig > max_size {
+        let magn = (w_orig as f64 / max_size as f64 ).max(h_orig as f64 / max_size as f64 ).ceil();
+        let img = image.thumbnail((w_orig as f64/magn) as u32, (h_orig as f64/magn) as u32);
+        //let img = image.resize( max_size, max_size, image::imageops::FilterType::Triangle, );
+        let resize = w_orig as f32 / img.width() as f32;
+        Some((img, resize))
+    }
+    else {
+        None
+    }
+}
+
+/// Forgatás, RGBA konverzió és (ha van LUT) színkorrekció.
+pub fn rotate_and_color(img: &image::DynamicImage, colset: &ColorSettings, lut: Option<&Lut4ColorSettings>) -> image::RgbaImage {
+    let mut rgba_image = match colset.rotate {
+        Rotate::Rotate90  => img.rotate90().to_rgba8(),
+        Rotate::Rotate180 => img.rotate180().to_rgba8(),
+        Rotate::Rotate270 => img.rotate270().to_rgba8(),
+        _ => img.to_rgba8(),
+    };
+    if (colset.is_setted() || colset.is_blured()) && let Some(lut) = lut {
+        lut.apply_lut(&mut rgba_image);
+    }
+    rgba_image
+}
+
+/// Az első megjelenítés előkészítése, a UI száltól függetlenül.
+pub fn prepare_view(image: &image::DynamicImage, colset: &ColorSettings, max_size: u32) -> PreparedView {
+    let fitted = fit_to_texture(image, max_size);
+    let source = fitted.as_ref().map(|(small, _)| small).unwrap_or(image);
+    let lut = if colset.is_setted() || colset.is_blured() {
+        let mut lut = Lut4ColorSettings::default();
+        lut.update_lut(colset);
+        Some(lut)
+    } else {
+        None
+    };
+    let rgba_image = rgba_to_buffer(&rotate_and_color(source, colset, lut.as_ref()));
+    let (resized_image, resize, pyramid) = match fitted {
+        Some((small, resize)) => (Some(small), resize, Some(TilePyramid::new(image))),
+        None => (None, 1.0, None),
+    };
+    PreparedView { color_settings: *colset, resized_image, resize, rgba_image, pyramid }
 }
 
 impl ImageViewer {
 
-    pub fn review(&mut self, coloring: bool, new_rotate: bool) {
+    /// A színkorrekciós LUT frissítése; `coloring == false` esetén a beállítások alaphelyzetbe állnak.
+    pub fn update_lut(&mut self, coloring: bool) {
         let default_settings = ColorSettings::default();
         if coloring {
-            if let Some(_interface) = &self.gpu_interface {
-            }
-            else {
-                let lut_ref = self.lut.get_or_insert_with(Lut4ColorSettings::default);
-                lut_ref.update_lut( if self.show_original_only { &default_settings} else { &self.color_settings} );
+            // a GPU eszköz csak az első tényleges színkorrekciónál készül, az indulást nem lassítja
+            if self.use_gpu && !self.gpu_tried_init && !self.show_original_only
+                    && (self.color_settings.is_setted() || self.color_settings.is_blured()) {
+                self.gpu_tried_init = true;
+                self.gpu_interface = crate::gpu_colors::GpuInterface::gpu_init();
             }
+            // a CPU LUT GPU mellett is kell: csempékhez, és ha a kép túl nagy a GPU-nak
+            let lut_ref = self.lut.get_or_insert_with(Lut4ColorSettings::default);
+            lut_ref.update_lut( if self.show_original_only { &default_settings} else { &self.color_settings} );
         } else {
             self.lut = None;
             self.color_settings = default_settings.clone();
         }
+    }
 
-        let mut img : & mut image::DynamicImage = &mut Default::default();
-        if let Some(mut resized_image) = self.resized_image.clone() {
-            *img = resized_image;
-        }
-        else {
-            if let Some(mut image) = self.original_image.clone() {
-                let w_orig = image.width();
-                let h_orig = image.height();
-                self.original_image_size = (w_orig,h_orig).into();
-                let max_gpu_size = 4096;// TODO !!! ctx.input(|i| i.max_texture_side) as u32;
-                if w_orig > max_gpu_size || h_orig > max_gpu_size {
-                    let magn = (w_orig as f64 / max_gpu_size as f64 ).max(h_orig as f64 / max_gpu_size as f64 ).ceil();
-                    *img = image.thumbnail((w_orig as f64/magn) as u32, (h_orig as f64/magn) as u32);
-                    //*img = image.resize( max_gpu_size, max_gpu_size, image::imageops::FilterType::Triangle, );
-                    self.resize =  w_orig as f32 / img.width() as f32;
-                    self.resized_image = Some(img.clone());
-                }
-                else {
-                    self.resize = 1.0;
-                    *img = image;
-                }
-            }
+    /// A megjelenítéshez ténylegesen használt beállítások (Show Original esetén az alapértelmezettek).
+    pub fn effective_color_settings(&self) -> ColorSettings {
+        if self.show_original_only {
+            let mut colset = ColorSettings::default();
+            colset.rotate = self.color_settings.rotate;
+            colset
+        } else {
+            self.color_settings
         }
+    }
+
+    fn update_modified(&mut self) {
         self.modified = !self.show_original_only &&
                 (self.color_settings.is_setted() || self.color_settings.is_blured());
-        match self.color_settings.rotate {
-            Rotate::Rotate90  => { *img = img.rotate90() ; self.modified = true; }, 
-            Rotate::Rotate180 => { *img = img.rotate180(); self.modified = true; },
-            Rotate::Rotate270 => { *img = img.rotate270(); self.modified = true; },
-            _ => {}
+        if self.color_settings.rotate != Rotate::Rotate0 {
+            self.modified = true;
         }
-        if new_rotate {
-            self.want_magnify = -1.0; // modified image width:height ratio
+    }
+
+    /// Új eredeti kép (betöltés, animáció képkocka); a megjelenítési cache ettől érvénytelen.
+    pub fn set_original_image(&mut self, image: Arc<image::DynamicImage>) {
+        self.original_image = Some(image);
+        self.image_generation += 1;
+        self.frame_cache.shown = None;
+    }
+
+    fn base_key(&self) -> BaseKey {
+        BaseKey {
+            generation: self.image_generation,
+            resized: self.resized_image.is_some(),
+            rotate: self.color_settings.rotate,
         }
+    }
 
-        let mut rgba_image = img.to_rgba8();
-        let (width, height) = rgba_image.dimensions();
-        self.image_size = ( width, height ).into();
-        
+    /// A színkorrigált kép kulcsa; a review és a review_prepared is ezzel jegyzi a cache-ben.
+    fn corrected_key(&self) -> CorrectedKey {
+        CorrectedKey {
+            base: self.base_key(),
+            colset: self.effective_color_settings(),
+            gpu: self.gpu_interface.is_some(),
+        }
+    }
+
+    /// Színkorrekció a GPU-n, ha van és elfér rajta a kép, különben a CPU LUT-tal.
+    pub fn color_correct(&self, colset: &ColorSettings, rgba: &mut [u8], width: u32, height: u32) {
         if let Some(interface) = &self.gpu_interface {
-            interface.change_colorcorrection(
-                if self.show_original_only { &default_settings } else { &self.color_settings },
-                self.image_size.x,
-                self.image_size.y);
+            interface.change_colorcorrection(colset, width as f32, height as f32);
+            if interface.generate_image(rgba, width, height) {
+                return;
+            }
+        }
+        if let Some(lut) = &self.lut {
+            lut.apply_lut_raw(rgba, width, height);
         }
+    }
 
-        
-        if self.color_settings.is_setted() || self.color_settings.is_blured() {
-            if self.gpu_interface.is_some() {
-                
-                self.gpu_interface.as_ref().unwrap().generate_image(rgba_image.as_mut(), width, height);
-                            } else if let Some(lut) = &self.lut {
-                lut.apply_lut(&mut rgba_image); 
+    pub fn review(&mut self, coloring: bool, new_rotate: bool) {
+        self.update_lut(coloring);
+
+        if self.resized_image.is_none() && let Some(image) = &self.original_image {
+            self.original_image_size = (image.width(), image.height()).into();
+            if let Some((small, resize)) = fit_to_texture(image, self.max_texture_size) {
+                self.resize = resize;
+                self.resized_image = Some(small);
+                self.tiles = Some(TiledView::new(TilePyramid::new(image)));
+            }
+            else {
+                self.resize = 1.0;
             }
         }
-        self.rgba_image = Some(rgba_image.clone());
-        
+        self.update_modified();
+        if new_rotate {
+            self.want_magnify = -1.0; // modified image width:height ratio
+        }
+
+        // csak azok a lépések készülnek újra, amelyek bemenete változott (nagyításnál egyik sem)
+        let key = self.corrected_key();
+        let colset = key.colset;
+        let frame_key = FrameKey { colset, gpu: key.gpu, resized: key.base.resized };
+        if !self.view_cache.has_corrected(&key) {
+            // animáció képkocka, ami egy korábbi körben már elkészült
+            if let Some(rgba) = self.frame_cache.get(&frame_key) {
+                self.view_cache.set_prepared(key, rgba, true);
+                self.show_cached();
+                return;
+            }
+            if !self.view_cache.has_base(&key.base) {
+                let empty = image::DynamicImage::default();
+                let img = self.resized_image.as_ref().or(self.original_image.as_deref()).unwrap_or(&empty);
+                self.view_cache.set_base(key.base, rgba_to_buffer(&rotate_and_color(img, &colset, None)));
+            }
+            let corrected = if colset.is_setted() || colset.is_blured() {
+                self.view_cache.base().map(|base| {
+                    // a base megosztott: az első írás készít belőle saját másolatot
+                    let mut buffer = base.clone();
+                    let (width, height) = (buffer.width(), buffer.height());
+                    self.color_correct(&colset, buffer.make_mut_bytes(), width, height);
+                    buffer
+                })
+            } else {
+                None
+            };
+            self.view_cache.set_corrected(key, corrected);
+            if let Some(rgba) = self.view_cache.rgba() {
+                self.frame_cache.insert(&frame_key, rgba, self.config.anim_cache_mb * 1024 * 1024);
+            }
+        }
+
+        self.show_cached();
+    }
+
+    /// Csúszka húzása közben: csak a látható rész színkorrekciója (kicsinyítésnél képernyő méretű
+    /// proxyból), egyetlen csempeként a kép fölé rajzolva. A teljes kép a review()-ban készül el.
+    pub fn review_interactive(&mut self) {
+        self.update_lut(true);
+        self.update_modified();
+        let colset = self.effective_color_settings();
+        let Some(handle) = &self.ui_handle else { return; };
+        let Some(ui) = handle.upgrade() else { return; };
+        let Some(img) = self.resized_image.as_ref().or(self.original_image.as_deref()) else { return; };
+        let (img_w, img_h) = match colset.rotate {
+            Rotate::Rotate90 | Rotate::Rotate270 => (img.height(), img.width()),
+            _ => (img.width(), img.height()),
+        };
+        let state = ui.get_img_state();
+        if state.viewport_width < 1.0 || state.viewport_height < 1.0 {
+            return;
+        }
+        // látható terület viewport koordinátákban
+        let vx0 = (-ui.get_offset_x()).clamp(0.0, state.viewport_width);
+        let vy0 = (-ui.get_offset_y()).clamp(0.0, state.viewport_height);
+        let vx1 = (vx0 + state.window_width).min(state.viewport_width);
+        let vy1 = (vy0 + state.window_height).min(state.viewport_height);
+        // ugyanez a (forgatott) forrás kép pixeleiben
+        let fx = img_w as f32 / state.viewport_width;
+        let fy = img_h as f32 / state.viewport_height;
+        let x = (vx0 * fx).floor() as u32;
+        let y = (vy0 * fy).floor() as u32;
+        let x1 = ((vx1 * fx).ceil() as u32).min(img_w);
+        let y1 = ((vy1 * fy).ceil() as u32).min(img_h);
+        if x1 <= x || y1 <= y {
+            return;
+        }
+        let (tw, th) = (x1 - x, y1 - y);
+        let dw = ((vx1 - vx0).ceil() as u32).max(1);
+        let dh = ((vy1 - vy0).ceil() as u32).max(1);
+
+        let lut = if colset.is_setted() || colset.is_blured() { self.lut.as_ref() } else { None };
+        let image = if tw <= dw && th <= dh {
+            build_tile(img, &colset, lut, x, y, tw, th)
+        } else {
+            let (sx, sy, sw, sh) = source_rect(colset.rotate, x, y, tw, th, img.width(), img.height());
+            let (pw, ph) = match colset.rotate {
+                Rotate::Rotate90 | Rotate::Rotate270 => (dh, dw),
+                _ => (dw, dh),
+            };
+            let proxy = img.crop_imm(sx, sy, sw, sh).resize_exact(pw, ph, image::imageops::FilterType::Triangle);
+            let rgba = rotate_and_color(&proxy, &colset, lut);
+            Image::from_rgba8(SharedPixelBuffer::<Rgba8Pixel>::clone_from_slice(rgba.as_raw(), rgba.width(), rgba.height()))
+        };
+        let preview = TileSlint {
+            x: x as f32 / fx,
+            y: y as f32 / fy,
+            width: tw as f32 / fx,
+            height: th as f32 / fy,
+            image,
+        };
+        ui.set_tiles(ModelRc::new(VecModel::from(vec![preview])));
+    }
+
+    /// A háttérszálon már előkészített nézet átvétele; ha közben változtak a beállítások, újraszámol.
+    pub fn review_prepared(&mut self, view: PreparedView, coloring: bool) {
+        self.update_lut(coloring);
+        if self.gpu_interface.is_some() || view.color_settings != self.effective_color_settings() {
+            self.review(coloring, false);
+            return;
+        }
+        if let Some(image) = &self.original_image {
+            self.original_image_size = (image.width(), image.height()).into();
+        }
+        self.resize = view.resize;
+        self.resized_image = view.resized_image;
+        self.tiles = view.pyramid.map(TiledView::new);
+        self.update_modified();
+        let key = self.corrected_key();
+        let colset = key.colset;
+        self.view_cache.set_prepared(key, view.rgba_image, colset.is_setted() || colset.is_blured());
+        self.show_cached();
+    }
 
-        let slint_pixel_buffer = SharedPixelBuffer::<Rgba8Pixel>::clone_from_slice(
-            rgba_image.as_raw(), 
-            width, 
-            height
-        );
-        let slint_img = Image::from_rgba8(slint_pixel_buffer);
+    /// A cache-elt kép átadása a Slint-nek, ablakméretezés és animáció időzítés.
+    fn show_cached(&mut self) {
+        let Some(slint_img) = self.view_cache.image() else { return; };
+        let (width, height) = self.view_cache.rgba().map(|rgba| (rgba.width(), rgba.height())).unwrap_or((0, 0));
+        // csempézett képnél a nagyítás a teljes felbontásra vonatkozik, a kicsinyített kép csak kitölti
+        self.image_size = match &self.tiles {
+            Some(tiled) => tiled.pyramid.rotated_size(self.color_settings.rotate).into(),
+            None => ( width, height ).into(),
+        };
         
         self.sizing_and_show_window(slint_img);
+        self.enforce_memory_budget();
+        self.update_onion_skin();
         
         set_animation(self);
     }
 
+    /// A renderer felállása után érkező textúra méret; `None`, ha nem kérdezhető le (szoftveres renderer,
+    /// vagy a femtovg GL környezete nem adott értéket), ilyenkor a MAX_TEXTURE_SIZE marad.
+    /// Ha a határ változott, az aktuális kép újra előkészül.
+    pub fn set_max_texture_size(&mut self, size: Option<u32>) {
+        let size = size.unwrap_or(MAX_TEXTURE_SIZE);
+        if size == self.max_texture_size {
+            return;
+        }
+        self.max_texture_size = size;
+        self.prefetch.clear();
+        self.view_cache.clear();
+        if self.original_image.is_some() {
+            self.resized_image = None;
+            self.tiles = None;
+            self.review(true, false);
+        }
+    }
+
+    /// Betöltés közben a cím és egy felirat jelzi, hogy a kép még nem érkezett meg.
+    pub fn show_loading(&self, loading: bool) {
+        if let Some(handle) = &self.ui_handle && let Some(ui) = handle.upgrade() {
+            ui.set_loading(loading);
+            if loading {
+                let mut state = ui.get_img_state();
+                state.window_title = format!("iViewer - {}. {}   loading ...",
+                    self.actual_index, self.image_name).into();
+                ui.set_img_state(state);
+            }
+        }
+    }
+
+
+    /// Nem modális értesítés az ablak alján; kattintásra vagy NOTIFY_SECONDS után eltűnik.
+    pub fn notify_error(&self, error: &ViewerError) {
+        self.notify(&error.to_string());
+    }
+
+    /// Üzenet a kép alján sávban, NOTIFY_SECONDS után eltűnik.
+    pub fn notify(&self, text: &str) {
+        if let Some(handle) = &self.ui_handle && let Some(ui) = handle.upgrade() {
+            ui.set_notification(text.into());
+            let weak = handle.clone();
+            self.notify_timer.start(slint::TimerMode::SingleShot,
+                std::time::Duration::from_secs(NOTIFY_SECONDS), move || {
+                if let Some(ui) = weak.upgrade() {
+                    ui.set_notification("".into());
+                }
+            });
+        }
+    }
+
+    pub fn clear_notification(&self) {
+        self.notify_timer.stop();
+        if let Some(handle) = &self.ui_handle && let Some(ui) = handle.upgrade() {
+            ui.set_notification("".into());
+        }
+    }
+
+    /// Az ablak címe: sorszám, név, módosítás jele, nagyítás, animációnál a képkocka, többoldalas fájlnál az oldal.
+    pub fn window_title(&self) -> slint::SharedString {
+        let mut title = format!("iViewer - {}. {}{}   {}",
+            self.actual_index, self.image_name, if self.modified {'*'} else {' '},  self.magnify);
+        if let Some(anim) = &self.anim_data {
+            title = format!("{} Frame: {} / {}",title, self.current_frame + 1, anim.total_frames);
+        }
+        if self.page_count > 1 {
+            title = format!("{} Page: {} / {}",title, self.current_page + 1, self.page_count);
+        }
+        title.into()
+    }
+
+    /// Sikertelen betöltés után: az útvonal, név és cím visszaáll a még látható képre.
+    /// Az actual_index marad, így a lapozás átléphet a hibás fájlon.
+    pub fn restore_shown_image(&mut self) {
+        if let Some(path) = self.shown_image.clone() {
+            if let Ok(format) = detect_format(&path) {
+                self.image_format = format;
+            }
+            if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
+                self.image_name = file_name.to_string();
+            }
+            self.image_full_path = Some(path);
+        }
+        if let Some(handle) = &self.ui_handle && let Some(ui) = handle.upgrade() {
+            let mut state = ui.get_img_state();
+            state.window_title = self.window_title();
+            ui.set_img_state(state);
+        }
+        set_animation(self);
+    }
 
     fn sizing_and_show_window(&mut self, slint_img: slint::Image){
 
@@ -198,11 +560,16 @@ impl ImageViewer {
             let ratio = display_size_netto / self.image_size; // divide by tags
             self.magnify = ratio.x.min(ratio.y);
 
-            if !self.rgba_image.is_some() {
+            if self.view_cache.rgba().is_none() {
                 self.magnify *= 0.5; // empty window
             }
             //let round_ = if self.magnify < 1.0 { 0.0 } else { 0.5 };
-            self.magnify = (((self.magnify * 20.0 ) as i32) as f32) / 20.0;
+            if self.magnify >= 0.1 {
+                self.magnify = (((self.magnify * 20.0 ) as i32) as f32) / 20.0;
+            }
+            else { // nagyon nagy (csempézett) kép
+                self.magnify = ((((self.magnify * 100.0 ) as i32) as f32) / 100.0).max(0.01);
+            }
         }
 
         if self.change_magnify != 0.0 || self.want_magnify > 0.009 {
@@ -216,7 +583,8 @@ impl ImageViewer {
                 else if self.magnify >= 4.0 {
                     self.change_magnify *= 2.0;
                 }
-                self.magnify = (old_magnify * 1.0 + (0.05 * self.change_magnify)).clamp(0.1, 10.0);
+                let min_magnify = if self.tiles.is_some() { 0.01 } else { 0.1 };
+                self.magnify = (old_magnify * 1.0 + (0.05 * self.change_magnify)).clamp(min_magnify, 10.0);
                 self.magnify = (((self.magnify * 100.0 + 0.5) as i32) as f32) / 100.0; // round
             }
             bigger = self.magnify / old_magnify;
@@ -250,11 +618,7 @@ impl ImageViewer {
             if let Some(handle) = &self.ui_handle {
                 if let Some(ui) = handle.upgrade() {
                     //println!("{:?} {:?} {:?} {:?} {:?} ", inner_size, pos, off, self.magnify, self.center);
-                    let mut title: slint::SharedString = format!("iViewer - {}. {}{}   {}",
-                        self.actual_index, self.image_name, if self.modified {'*'} else {' '},  self.magnify).into();
-                    if let Some(anim) = &self.anim_data {
-                        title = format!("{} Frame: {} / {}",title, self.current_frame + 1, anim.total_frames).into();
-                    }
+                    let title = self.window_title();
                     if bigger != 1.0 || self.want_magnify == -1.0 {
                         ui.window().set_position(slint::PhysicalPosition::new(pos.x as i32, pos.y as i32));
                     }
@@ -290,15 +654,14 @@ impl ImageViewer {
         self.aktualis_offset = off;
         self.want_magnify = 0.0;
         self.change_magnify = 0.0;
+        self.update_tiles();
     }
 
 
     pub fn pick_color(&self, pixel_x : u32,pixel_y: u32) -> Option<Color> {
-        if let Some(rgba_image) = &self.rgba_image {
-            if pixel_x < rgba_image.width() && pixel_y < rgba_image.height() {
-                let pixel = rgba_image.get_pixel(pixel_x, pixel_y);
-                return Some(Color::from_rgb_u8(pixel[0], pixel[1], pixel[2]));
-            }
+        if let Some(rgba_image) = self.view_cache.rgba() && pixel_x < rgba_image.width() && pixel_y < rgba_image.height() {
+            let pixel = rgba_image.as_slice()[(pixel_y * rgba_image.width() + pixel_x) as usize];
+            return Some(Color::from_rgb_u8(pixel.r, pixel.g, pixel.b));
         }
         None
     }
@@ -314,6 +677,7 @@ impl ImageViewer {
             (self.actual_index + self.list_of_images.len() - 1) % self.list_of_images.len()
         };
         self.actual_index = uj_index;
+        self.prefetch.direction = irany;
         self.open_image(&self.list_of_images[uj_index].path(), false);
     }
 
@@ -5,12 +5,37 @@ mod gpu_colors;
 mod colors;
 mod file_handlers;
 mod image_processing;
+mod image_loader;
+mod prefetch;
+mod tiles;
+mod view_cache;
+mod frame_cache;
+mod memory;
+mod errors;
+mod atomic_save;
+mod modern_formats;
+mod raw_image;
+mod pages;
+mod anim_save;
+mod anim_stream;
+mod timeline;
+mod anim_edit;
+mod assemble;
+mod onion;
 mod exif_my;
 mod pf32;
 
 use pf32::*;
 use colors::*;
 use crate::image_processing::*;
+use crate::image_loader::*;
+use crate::prefetch::*;
+use crate::tiles::*;
+use crate::view_cache::*;
+use crate::frame_cache::*;
+use crate::onion::*;
+use crate::timeline::TimelineThumbs;
+use crate::anim_save::AnimSaveTask;
 use crate::file_handlers::*;
 use crate::exif_my::*;
 use std::fs;
@@ -25,6 +50,11 @@ fn main() -> Result<(), slint::PlatformError> {
 		//std::env::set_var("SLINT_DEBUG_PERFORMANCE", "console,refresh_lazy");
 	}
     //let icon = load_icon();
+    // femtovg (OpenGL) ha lehet, különben szoftveres renderelés
+    if let Err(err) = slint::BackendSelector::new().renderer_name("femtovg".into()).select() {
+        println!("OpenGL renderer not available ({err}), using software renderer");
+        slint::BackendSelector::new().renderer_name("software".into()).select()?;
+    }
     let ui = MainWindow::new()?;
 
 	unsafe {
@@ -37,11 +67,13 @@ fn main() -> Result<(), slint::PlatformError> {
     let about_ui = AboutWindow::new()?;
     let info_ui = InfoWindow::new()?;
     let save_window_ui = SaveWindow::new()?;
+    let timeline_ui = TimelineWindow::new()?;
     let state = Rc::new(RefCell::new(ImageViewer::default()));
     state.borrow_mut().ui_handle = Some(ui.as_weak());
     state.borrow_mut().self_weak = Some(state.clone());
+    state.borrow_mut().assemble_window = Some(AssembleWindow::new()?);
     
-    file_callbacks::file_callbacks(ui.as_weak(), settings_ui, about_ui, info_ui, save_window_ui, state.clone());
+    file_callbacks::file_callbacks(ui.as_weak(), settings_ui, about_ui, info_ui, save_window_ui, timeline_ui, state.clone());
     
     let res = ui.run();
     
@@ -57,11 +89,15 @@ pub struct ImageViewer {
     pub about_window: Option<AboutWindow>,
     pub info_window: Option<InfoWindow>,
     pub save_window: Option<SaveWindow>,
+    pub timeline_window: Option<TimelineWindow>,
+    pub assemble_window: Option<AssembleWindow>,
     pub show_settings: bool,
     pub show_info: bool,
     pub show_save: bool,
+    pub show_timeline: bool,
     
     pub image_full_path: Option<PathBuf>,
+    pub shown_image: Option<PathBuf>, // az utoljára sikeresen betöltött (látható) kép
     pub file_meta: Option<fs::Metadata>,
     pub exif: Option<ExifBlock>,
     pub image_name: String,
@@ -75,10 +111,12 @@ pub struct ImageViewer {
     pub resize: f32,
     
     // Slint kompatibilis kép tárolás
-    pub current_slint_image: Option<slint::Image>, 
-    pub original_image: Option<image::DynamicImage>,
+    pub original_image: Option<std::sync::Arc<image::DynamicImage>>, // animációnál a képkockával közös
     pub resized_image: Option<image::DynamicImage>,
-    pub rgba_image: Option<image::ImageBuffer<image::Rgba<u8>, Vec<u8>>>,
+    pub image_generation: u64, // minden új original_image-nél nő
+    pub view_cache: ViewCache, // a megjelenített kép lépései (forgatás, színkorrekció, Slint kép)
+    pub frame_cache: FrameCache, // animációnál a képkockák kész (színkorrigált) képe
+    pub tiles: Option<TiledView>, // csak a textúra méretnél nagyobb képeknél
     
     pub display_size: Pf32, 
     pub window_frame: Pf32, // title, menu, padding, rendszer tálca
@@ -96,6 +134,7 @@ pub struct ImageViewer {
     pub lut: Option<Lut4ColorSettings>,
     pub gpu_interface : Option<gpu_colors::GpuInterface>,
     pub gpu_tried_init: bool,
+    pub max_texture_size: u32, // a renderer által megengedett legnagyobb textúra oldal
     pub use_gpu: bool,
     pub refit_reopen: bool,
     pub fit_open: bool,
@@ -108,10 +147,27 @@ pub struct ImageViewer {
     pub is_animated: bool,
     pub anim_playing: bool,
     pub anim_loop: bool,
+    pub anim_speed: f32, // lejátszási sebesség szorzó (0.1 - 4.0)
+    pub anim_ping_pong: bool,
+    pub anim_reverse: bool, // ping-pong: éppen visszafelé játszik
+    pub anim_plays_done: u32, // a befejezett lejátszások (a fájl ismétlésszámához)
     pub anim_timer: slint::Timer,
+    pub interactive_timer: slint::Timer, // csúszka húzás utáni teljes újraszámolás
+    pub notify_timer: slint::Timer, // a hibaértesítés eltüntetése
     pub current_frame: usize,
-    pub last_frame_time: std::time::Instant,
+    pub current_page: usize, // többoldalas TIFF / ICO: a látható oldal
+    pub page_count: usize,
+    pub last_frame_time: std::time::Instant, // az aktuális képkocka esedékessége, ehhez ütemez a lejátszás
     pub anim_data: Option<AnimatedImage>,
+    pub assemble_files: Vec<PathBuf>, // animáció összeállításhoz kijelölt fájlok
+    pub assemble_cancel: Option<std::sync::Arc<std::sync::atomic::AtomicBool>>, // a futó összeállítás leállítása
+    pub page_export_cancel: Option<std::sync::Arc<std::sync::atomic::AtomicBool>>, // az oldalak mentésének leállítása
+    pub anim_save: Option<AnimSaveTask>, // a háttérszálon futó animáció mentés
+    pub frame_export_cancel: Option<std::sync::Arc<std::sync::atomic::AtomicBool>>, // a képkockák mentésének leállítása
+    pub timeline_thumbs: TimelineThumbs, // az idősáv bélyegképei a fájl képkockáihoz (szerkesztéskor nem kell újradekódolni)
+    pub onion: OnionSkin, // léptetéskor a szomszédos képkockák halványan a kép fölött
+    pub loader: ImageLoader,
+    pub prefetch: PrefetchCache,
     pub show_original_only: bool,
     pub modified: bool,
     // A többi meződet (settings, gpu, stb.) fokozatosan tölthetjük be
@@ -126,11 +182,15 @@ impl Default for ImageViewer {
             about_window: None,
             info_window: None,
             save_window: None,
+            timeline_window: None,
+            assemble_window: None,
             show_settings: false,
             show_info: false,
             show_save: false,
+            show_timeline: false,
             
             image_full_path: None,
+            shown_image: None,
             file_meta: None,
             exif: None,
             image_name: String::new(),
@@ -142,10 +202,12 @@ impl Default for ImageViewer {
             change_magnify: 0.0,
             want_magnify: -1.0,
             resize: 1.0,
-            current_slint_image: None,
             original_image: None,
             resized_image: None,
-            rgba_image: None,
+            image_generation: 0,
+            view_cache: ViewCache::default(),
+            frame_cache: FrameCache::default(),
+            tiles: None,
             display_size: Pf32{x:1280.0, y:1024.0},
             window_frame: Pf32{ x:10.0 , y:60.0 }, // title, menu, padding, rendszer tálca
             window_size: Pf32{x:800.0, y:600.0},
@@ -162,6 +224,7 @@ impl Default for ImageViewer {
             lut: None,
             gpu_interface : None,
             gpu_tried_init: false,
+            max_texture_size: MAX_TEXTURE_SIZE,
             use_gpu: true,
             refit_reopen: false,
             fit_open: true,
@@ -173,10 +236,27 @@ impl Default for ImageViewer {
             is_animated: false,
             anim_playing: false,
             anim_loop: true,
+            anim_speed: 1.0,
+            anim_ping_pong: false,
+            anim_reverse: false,
+            anim_plays_done: 0,
             anim_timer: slint::Timer::default(),
+            interactive_timer: slint::Timer::default(),
+            notify_timer: slint::Timer::default(),
             current_frame: 0,
+            current_page: 0,
+            page_count: 1,
             last_frame_time: std::time::Instant::now(),
             anim_data: None,
+            assemble_files: Vec::new(),
+            assemble_cancel: None,
+            page_export_cancel: None,
+            anim_save: None,
+            frame_export_cancel: None,
+            timeline_thumbs: TimelineThumbs::default(),
+            onion: OnionSkin::default(),
+            loader: ImageLoader::default(),
+            prefetch: PrefetchCache::default(),
             show_original_only: false,
             modified: false,
         }
@@ -0,0 +1,86 @@
+use crate::ImageViewer;
+
+// Memória nyilvántartás: a nagy pufferek (eredeti kép, kicsinyített kép, megjelenítési cache,
+// animáció képkockái, csempék, előtöltött képek) becsült mérete és a beállítható keret.
+// A keret túllépésekor először az újra előállítható dolgok kerülnek ki.
+
+#[derive(Default, Clone, Copy, Debug)]
+pub struct MemoryUsage {
+    pub original: usize,
+    pub resized: usize,
+    pub view: usize,
+    pub animation: usize,
+    pub tiles: usize,
+    pub prefetch: usize,
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> usize {
+        self.resident() + self.prefetch
+    }
+
+    /// Az aktuális kép által foglalt memória (előtöltés nélkül).
+    pub fn resident(&self) -> usize {
+        self.original + self.resized + self.view + self.animation + self.tiles
+    }
+}
+
+pub fn format_mb(bytes: usize) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+impl ImageViewer {
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            original: self.original_image.as_ref().map(|img| img.as_bytes().len()).unwrap_or(0),
+            resized: self.resized_image.as_ref().map(|img| img.as_bytes().len()).unwrap_or(0),
+            view: self.view_cache.bytes(),
+            animation: self.anim_data.as_ref().map(|anim| anim.bytes(self.original_image.as_ref())).unwrap_or(0)
+                + self.frame_cache.bytes(),
+            tiles: self.tiles.as_ref().map(|tiled| tiled.bytes()).unwrap_or(0),
+            prefetch: self.prefetch.bytes(),
+        }
+    }
+
+    pub fn memory_budget_bytes(&self) -> usize {
+        self.config.memory_budget_mb * 1024 * 1024
+    }
+
+    /// Az előtöltésre még jutó memória: a saját kerete, de legfeljebb ami a teljes keretből maradt.
+    pub fn prefetch_limit_bytes(&self) -> usize {
+        let limit = self.config.prefetch_memory_mb * 1024 * 1024;
+        match self.memory_budget_bytes() {
+            0 => limit,
+            budget => limit.min(budget.saturating_sub(self.memory_usage().resident())),
+        }
+    }
+
+    /// Keret túllépésekor sorban: előtöltött képek, animáció kész képkockái, kész csempék, a színkorrekció előtti kép.
+    /// Ami a megjelenítéshez kell (eredeti, megjelenített kép), az nem kerül ki.
+    pub fn enforce_memory_budget(&mut self) {
+        let budget = self.memory_budget_bytes();
+        if budget == 0 || self.memory_usage().total() <= budget {
+            return;
+        }
+        self.prefetch.clear();
+        if self.memory_usage().total() <= budget {
+            return;
+        }
+        self.frame_cache.clear();
+        if self.memory_usage().total() <= budget {
+            return;
+        }
+        if let Some(tiled) = &mut self.tiles {
+            tiled.clear_cache();
+        }
+        if self.memory_usage().total() <= budget {
+            return;
+        }
+        self.view_cache.drop_base();
+        let total = self.memory_usage().total();
+        if total > budget {
+            self.notify(&format!("Memory budget exceeded: {} / {}", format_mb(total), format_mb(budget)));
+        }
+    }
+}
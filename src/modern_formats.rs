@@ -0,0 +1,90 @@
+// AVIF és JPEG XL kódolás/dekódolás.
+// Az AVIF olvasását az image crate "avif-native" (dav1d) része végzi,
+// az írás a ravif-fel, a JPEG XL olvasás jxl-oxide-dal, az írás libjxl-lel (jpegxl-rs) megy.
+
+use image::{DynamicImage, ImageDecoder};
+use jpegxl_rs::encode::{EncoderResult, EncoderSpeed, Metadata};
+
+/// Alapértelmezett tömörítési ráfordítás (1-10).
+pub const DEFAULT_EFFORT: u8 = 6;
+
+/// JPEG XL fájl dekódolása a megadott korlátokkal.
+pub fn decode_jxl(path: &std::path::Path, limits: image::Limits) -> Result<DynamicImage, String> {
+    let file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+    let mut decoder = jxl_oxide::integration::JxlDecoder::new(std::io::BufReader::new(file))
+        .map_err(|e| e.to_string())?;
+    decoder.set_limits(limits).map_err(|e| e.to_string())?;
+    DynamicImage::from_decoder(decoder).map_err(|e| e.to_string())
+}
+
+/// AVIF kódolás; az exif a raw_exif ("Exif\0\0" fejléccel), a konténer a TIFF részt kapja.
+pub fn encode_avif(img: &DynamicImage, quality: u8, effort: u8, exif: Option<&[u8]>) -> Result<Vec<u8>, String> {
+    let rgba = img.to_rgba8();
+    let pixels: Vec<ravif::RGBA8> = rgba
+        .pixels()
+        .map(|p| ravif::RGBA8::new(p[0], p[1], p[2], p[3]))
+        .collect();
+    // a ravif sebessége 1 (lassú) .. 10 (gyors), a ráfordítás fordítva
+    let mut encoder = ravif::Encoder::new()
+        .with_quality(quality.clamp(1, 100) as f32)
+        .with_speed(11 - effort.clamp(1, 10));
+    if let Some(tiff) = exif.and_then(|raw| raw.get(6..)) {
+        encoder = encoder.with_exif(tiff);
+    }
+    let encoded = encoder
+        .encode_rgba(ravif::Img::new(&pixels[..], rgba.width() as usize, rgba.height() as usize))
+        .map_err(|e| e.to_string())?;
+    Ok(encoded.avif_file)
+}
+
+/// JPEG XL kódolás; veszteséges módban a minőséget libjxl távolsággá alakítja.
+pub fn encode_jxl(img: &DynamicImage, quality: u8, lossless: bool, effort: u8, exif: Option<&[u8]>) -> Result<Vec<u8>, String> {
+    let rgba = img.to_rgba8();
+    let mut encoder = jpegxl_rs::encoder_builder()
+        .has_alpha(true)
+        .lossless(lossless)
+        .quality(if lossless { 0.0 } else { jxl_distance(quality) })
+        .speed(jxl_speed(effort))
+        .use_container(exif.is_some())
+        .build()
+        .map_err(|e| e.to_string())?;
+    if let Some(tiff) = exif.and_then(|raw| raw.get(6..)) {
+        // az Exif doboz elején 4 bájtos eltolás áll a TIFF fejlécig
+        let mut boxed = vec![0u8; 4];
+        boxed.extend_from_slice(tiff);
+        encoder
+            .add_metadata(&Metadata::Exif(&boxed), true)
+            .map_err(|e| e.to_string())?;
+    }
+    let result: EncoderResult<u8> = encoder
+        .encode::<u8, u8>(rgba.as_raw(), rgba.width(), rgba.height())
+        .map_err(|e| e.to_string())?;
+    Ok(result.data)
+}
+
+/// A libjxl "cjxl -q" leképezése: 100 -> 0 (veszteségmentes közeli), 90 -> 1.0, 30 -> 6.4.
+fn jxl_distance(quality: u8) -> f32 {
+    let q = quality.clamp(1, 100) as f32;
+    if q >= 100.0 {
+        0.0
+    } else if q >= 30.0 {
+        0.1 + (100.0 - q) * 0.09
+    } else {
+        53.0 / 3000.0 * q * q - 23.0 / 20.0 * q + 25.0
+    }
+}
+
+fn jxl_speed(effort: u8) -> EncoderSpeed {
+    match effort.clamp(1, 10) {
+        1 => EncoderSpeed::Lightning,
+        2 => EncoderSpeed::Thunder,
+        3 => EncoderSpeed::Falcon,
+        4 => EncoderSpeed::Cheetah,
+        5 => EncoderSpeed::Hare,
+        6 => EncoderSpeed::Wombat,
+        7 => EncoderSpeed::Squirrel,
+        8 => EncoderSpeed::Kitten,
+        9 => EncoderSpeed::Tortoise,
+        _ => EncoderSpeed::Glacier,
+    }
+}
@@ -0,0 +1,149 @@
+// Onion skin: léptetéskor az előző és a következő képkocka halványan, színezve az aktuális fölött
+// (remegés, elcsúszás keresése sprite-okban és GIF exportokban). A Slint a fő kép fölé, ugyanarra
+// a területre rajzolja, így bármely nagyításnál a helyén van, a megjelenítési cache-t pedig nem érinti.
+// Lejátszás közben nem látszik.
+
+use std::sync::Arc;
+use std::time::Duration;
+use image::DynamicImage;
+use slint::{Image, Rgba8Pixel, SharedPixelBuffer};
+
+use crate::ImageViewer;
+use crate::colors::*;
+use crate::image_processing::{fit_to_texture, rotate_and_color};
+
+/// Ennyiszer néz vissza a még dekódolás alatt álló szomszédért (30 ms-onként).
+const ONION_RETRIES: u32 = 20;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct OnionKey {
+    prev: Option<usize>, // a látható szomszédok sorszáma a fájlban
+    next: Option<usize>,
+    rotate: Rotate,
+    prev_tint: [u8; 3],
+    next_tint: [u8; 3],
+}
+
+#[derive(Default)]
+pub struct OnionSkin {
+    pub enabled: bool,
+    key: Option<OnionKey>,    // a látható rétegek; None: nincs réteg
+    current: Option<usize>,   // ehhez a képkockához készültek
+    retries: u32,
+    timer: slint::Timer,
+}
+
+impl ImageViewer {
+
+    /// Animation -> Onion Skin (Shift+O).
+    pub fn toggle_onion_skin(&mut self) {
+        self.onion.enabled = !self.onion.enabled;
+        self.sync_onion_controls();
+        self.update_onion_skin();
+    }
+
+    /// Az idősáv beállításai: be / ki, átlátszatlanság és a két színezés.
+    pub fn set_onion_skin(&mut self, enabled: bool, opacity: f32, prev_tint: [u8; 3], next_tint: [u8; 3]) {
+        self.onion.enabled = enabled;
+        self.config.onion_opacity = opacity.clamp(0.05, 1.0);
+        self.config.onion_prev_tint = prev_tint;
+        self.config.onion_next_tint = next_tint;
+        self.sync_onion_controls();
+        self.update_onion_skin();
+    }
+
+    /// A menü pipája és az idősáv vezérlői a beállítások szerint.
+    pub fn sync_onion_controls(&self) {
+        if let Some(ui) = self.ui_handle.as_ref().and_then(|h| h.upgrade()) {
+            ui.set_onion_checked(self.onion.enabled);
+            ui.set_onion_opacity(self.config.onion_opacity);
+        }
+        if let Some(t_ui) = &self.timeline_window {
+            let [r, g, b] = self.config.onion_prev_tint;
+            t_ui.set_onion_prev(slint::Color::from_rgb_u8(r, g, b));
+            let [r, g, b] = self.config.onion_next_tint;
+            t_ui.set_onion_next(slint::Color::from_rgb_u8(r, g, b));
+            t_ui.set_onion(self.onion.enabled);
+            t_ui.set_onion_opacity(self.config.onion_opacity);
+        }
+    }
+
+    /// A szomszédos képkockák rétegei az aktuális kocka és forgatás szerint; csak a változás készül újra.
+    pub fn update_onion_skin(&mut self) {
+        let Some(anim) = self.anim_data.as_ref()
+            .filter(|anim| self.onion.enabled && !self.anim_playing && anim.total_frames > 1) else {
+            self.clear_onion_skin();
+            return;
+        };
+        // a léptetéshez igazodva körbe: az első kocka előtti az utolsó
+        let total = anim.total_frames;
+        let current = self.current_frame.min(total - 1);
+        let prev_src = anim.source_index((current + total - 1) % total);
+        let next_src = anim.source_index((current + 1) % total);
+        let prev = prev_src.and_then(|i| anim.frames.peek(i));
+        let next = next_src.and_then(|i| anim.frames.peek(i));
+        let missing = prev.is_none() || next.is_none();
+        let failed = anim.frames.failed();
+
+        if self.onion.current != Some(current) {
+            self.onion.current = Some(current);
+            self.onion.retries = 0;
+        }
+        // a még dekódolás alatt álló szomszédért rövidesen visszajön
+        if missing && !failed && self.onion.retries < ONION_RETRIES && let Some(state_rc) = self.self_weak.clone() {
+            self.onion.retries += 1;
+            self.onion.timer.start(slint::TimerMode::SingleShot, Duration::from_millis(30), move || {
+                state_rc.borrow_mut().update_onion_skin();
+            });
+        }
+
+        let rotate = self.effective_color_settings().rotate;
+        let key = OnionKey {
+            prev: prev.as_ref().and(prev_src),
+            next: next.as_ref().and(next_src),
+            rotate,
+            prev_tint: self.config.onion_prev_tint,
+            next_tint: self.config.onion_next_tint,
+        };
+        if self.onion.key == Some(key) {
+            return;
+        }
+        let layer = |frame: Option<Arc<DynamicImage>>, tint: [u8; 3]| {
+            frame.map_or_else(Image::default, |frame| onion_image(&frame, rotate, tint, self.max_texture_size))
+        };
+        let prev_image = layer(prev, key.prev_tint);
+        let next_image = layer(next, key.next_tint);
+        self.set_onion_images(prev_image, next_image);
+        self.onion.key = Some(key);
+    }
+
+    /// A rétegek eltüntetése (lejátszás, új kép, kikapcsolás).
+    pub fn clear_onion_skin(&mut self) {
+        self.onion.timer.stop();
+        self.onion.current = None;
+        if self.onion.key.take().is_some() {
+            self.set_onion_images(Image::default(), Image::default());
+        }
+    }
+
+    fn set_onion_images(&self, prev: Image, next: Image) {
+        if let Some(ui) = self.ui_handle.as_ref().and_then(|h| h.upgrade()) {
+            ui.set_onion_prev(prev);
+            ui.set_onion_next(next);
+        }
+    }
+}
+
+/// A szomszéd a megjelenített kép méretében és forgatásával, félig a színezés felé keverve.
+fn onion_image(frame: &DynamicImage, rotate: Rotate, tint: [u8; 3], max_size: u32) -> Image {
+    let fitted = fit_to_texture(frame, max_size);
+    let source = fitted.as_ref().map(|(small, _)| small).unwrap_or(frame);
+    let colset = ColorSettings { rotate, ..ColorSettings::default() };
+    let mut rgba = rotate_and_color(source, &colset, None);
+    for pixel in rgba.pixels_mut() {
+        for (c, t) in pixel.0.iter_mut().zip(tint) {
+            *c = ((*c as u16 + t as u16) / 2) as u8;
+        }
+    }
+    Image::from_rgba8(SharedPixelBuffer::<Rgba8Pixel>::clone_from_slice(rgba.as_raw(), rgba.width(), rgba.height()))
+}
@@ -0,0 +1,234 @@
+// Többoldalas konténerek: TIFF oldalak (IFD lánc) és ICO/CUR képek.
+// Egy oldal dekódolásához a fájl fejlécét olvasás közben "átírjuk", hogy az image crate
+// a kért oldalt lássa elsőként; így minden színtípust és a dekódolási korlátokat is ő kezel.
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use image::{DynamicImage, ImageDecoder};
+
+use crate::ImageViewer;
+use crate::atomic_save::save_png;
+use crate::errors::*;
+use crate::file_handlers::SaveFormat;
+use crate::image_processing::worker_status;
+
+/// Olvasó, amely a fájl első bájtjai helyett a megadott fejlécet adja vissza.
+struct HeaderOverlay<R> {
+    inner: R,
+    header: Vec<u8>,
+    pos: u64,
+}
+
+impl<R: Read> Read for HeaderOverlay<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        let start = self.pos as usize;
+        if start < self.header.len() {
+            let end = (start + n).min(self.header.len());
+            buf[..end - start].copy_from_slice(&self.header[start..end]);
+        }
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for HeaderOverlay<R> {
+    fn seek(&mut self, from: SeekFrom) -> std::io::Result<u64> {
+        self.pos = self.inner.seek(from)?;
+        Ok(self.pos)
+    }
+}
+
+fn overlay_reader(path: &Path, header: Vec<u8>) -> Result<BufReader<HeaderOverlay<File>>, String> {
+    let inner = File::open(path).map_err(|e| e.to_string())?;
+    Ok(BufReader::new(HeaderOverlay { inner, header, pos: 0 }))
+}
+
+/// Az oldalak száma; egyoldalas vagy más formátumú fájlnál 1.
+pub fn page_count(path: &Path, format: SaveFormat) -> usize {
+    match format {
+        SaveFormat::Tif => tiff_page_offsets(path).map_or(1, |offsets| offsets.len().max(1)),
+        SaveFormat::Ico => ico_entries(path).map_or(1, |entries| entries.len().max(1)),
+        _ => 1,
+    }
+}
+
+/// A kért oldal dekódolása (0-tól számozva).
+pub fn decode_page(path: &Path, format: SaveFormat, page: usize, limits: &image::Limits) -> Result<DynamicImage, String> {
+    match format {
+        SaveFormat::Tif => {
+            let offsets = tiff_page_offsets(path)?;
+            let offset = *offsets.get(page).ok_or("No such page")?;
+            let mut header = [0u8; 16];
+            File::open(path).and_then(|mut f| f.read_exact(&mut header)).map_err(|e| e.to_string())?;
+            let little = &header[0..2] == b"II";
+            if header[2..4] == [43, 0] || header[2..4] == [0, 43] {
+                // BigTIFF: 8 bájtos első IFD cím a 8. bájttól
+                let bytes = if little { offset.to_le_bytes() } else { offset.to_be_bytes() };
+                header[8..16].copy_from_slice(&bytes);
+            } else {
+                let offset = offset as u32;
+                let bytes = if little { offset.to_le_bytes() } else { offset.to_be_bytes() };
+                header[4..8].copy_from_slice(&bytes);
+            }
+            let reader = overlay_reader(path, header.to_vec())?;
+            let mut decoder = image::codecs::tiff::TiffDecoder::new(reader).map_err(|e| e.to_string())?;
+            decoder.set_limits(limits.clone()).map_err(|e| e.to_string())?;
+            DynamicImage::from_decoder(decoder).map_err(|e| e.to_string())
+        }
+        SaveFormat::Ico => {
+            let entries = ico_entries(path)?;
+            let entry = entries.get(page).ok_or("No such image in the icon")?;
+            // egyelemes könyvtár: a kép címe abszolút, így a fájl többi része változatlan
+            let mut header = vec![0, 0, 1, 0, 1, 0];
+            header.extend_from_slice(entry);
+            let reader = overlay_reader(path, header)?;
+            let mut decoder = image::codecs::ico::IcoDecoder::new(reader).map_err(|e| e.to_string())?;
+            decoder.set_limits(limits.clone()).map_err(|e| e.to_string())?;
+            DynamicImage::from_decoder(decoder).map_err(|e| e.to_string())
+        }
+        _ => Err("This format has no pages".into()),
+    }
+}
+
+/// A TIFF oldalak IFD címei a fájl sorrendjében.
+fn tiff_page_offsets(path: &Path) -> Result<Vec<u64>, String> {
+    let file = BufReader::new(File::open(path).map_err(|e| e.to_string())?);
+    let mut decoder = tiff::decoder::Decoder::new(file).map_err(|e| e.to_string())?;
+    let mut offsets = Vec::new();
+    loop {
+        if let Some(pointer) = decoder.ifd_pointer() {
+            offsets.push(pointer.0);
+        }
+        if !decoder.more_images() || offsets.len() >= 10000 {
+            break;
+        }
+        decoder.next_image().map_err(|e| e.to_string())?;
+    }
+    Ok(offsets)
+}
+
+/// Az ICO/CUR könyvtár 16 bájtos bejegyzései, a legnagyobb kép elöl.
+fn ico_entries(path: &Path) -> Result<Vec<[u8; 16]>, String> {
+    let mut file = BufReader::new(File::open(path).map_err(|e| e.to_string())?);
+    let mut header = [0u8; 6];
+    file.read_exact(&mut header).map_err(|e| e.to_string())?;
+    let count = u16::from_le_bytes([header[4], header[5]]) as usize;
+    let mut entries = Vec::with_capacity(count);
+    for _ in 0..count {
+        let mut entry = [0u8; 16];
+        file.read_exact(&mut entry).map_err(|e| e.to_string())?;
+        entries.push(entry);
+    }
+    // 0 szélesség/magasság 256-ot jelent; azonos méretnél a nagyobb színmélység előbb
+    let side = |v: u8| if v == 0 { 256u32 } else { v as u32 };
+    entries.sort_by_key(|e| std::cmp::Reverse((side(e[0]) * side(e[1]), u16::from_le_bytes([e[6], e[7]]))));
+    Ok(entries)
+}
+
+impl ImageViewer {
+
+    /// Lapozás a többoldalas fájlban; a fájlon belül körbe jár.
+    pub fn change_page(&mut self, irany: i32) {
+        if self.page_count < 2 || self.shown_image != self.image_full_path {
+            return;
+        }
+        let page = (self.current_page as i64 + irany as i64).rem_euclid(self.page_count as i64) as usize;
+        if let Some(filepath) = self.shown_image.clone() {
+            self.start_load(filepath, true, false, page);
+        }
+    }
+
+    /// Az aktuális oldal mentése PNG-be (név_p001.png ajánlott névvel).
+    pub fn save_page(&mut self) {
+        let Some(source) = self.shown_image.clone() else {
+            return;
+        };
+        let stem = source.file_stem().and_then(|s| s.to_str()).unwrap_or("page");
+        let mut dialog = rfd::FileDialog::new()
+            .add_filter("PNG", &["png"])
+            .set_file_name(format!("{}_p{:03}.png", stem, self.current_page + 1));
+        if let Some(parent) = source.parent() {
+            dialog = dialog.set_directory(parent);
+        }
+        let Some(target) = dialog.save_file() else {
+            return;
+        };
+        let limits = self.config.decode_limits();
+        match export_page(&source, self.image_format, self.current_page, &limits, &target) {
+            Ok(()) => self.notify(&format!("Page {} saved to {}", self.current_page + 1, target.display())),
+            Err(e) => self.notify_error(&e),
+        }
+    }
+
+    /// Minden oldal mentése PNG-be a kiválasztott könyvtárba (név_p001.png, ...), háttérszálon;
+    /// a haladás az értesítő sávban látszik, Esc leállítja.
+    pub fn export_all_pages(&mut self) {
+        let Some(source) = self.shown_image.clone() else {
+            return;
+        };
+        if self.page_count < 2 {
+            return;
+        }
+        let Some(weak) = self.ui_handle.clone() else {
+            return;
+        };
+        if self.page_export_running() {
+            self.notify("Page export is already running");
+            return;
+        }
+        let mut dialog = rfd::FileDialog::new();
+        if let Some(parent) = source.parent() {
+            dialog = dialog.set_directory(parent);
+        }
+        let Some(dir) = dialog.pick_folder() else {
+            return;
+        };
+        let stem = source.file_stem().and_then(|s| s.to_str()).unwrap_or("page").to_string();
+        let limits = self.config.decode_limits();
+        let format = self.image_format;
+        let count = self.page_count;
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.page_export_cancel = Some(cancel.clone());
+        std::thread::spawn(move || {
+            let status = |text: String, done: bool| worker_status(&weak, text, done);
+            for page in 0..count {
+                if cancel.load(Ordering::SeqCst) {
+                    status(format!("Page export cancelled after {} pages", page), true);
+                    return;
+                }
+                status(format!("Exporting page {} / {} ... (Esc: cancel)", page + 1, count), false);
+                let target: PathBuf = dir.join(format!("{}_p{:03}.png", stem, page + 1));
+                if let Err(e) = export_page(&source, format, page, &limits, &target) {
+                    status(e.to_string(), true);
+                    return;
+                }
+            }
+            status(format!("{} pages exported to {}", count, dir.display()), true);
+        });
+    }
+
+    /// Fut-e még az oldalak mentése (a szál a saját példányát a végén elengedi).
+    fn page_export_running(&self) -> bool {
+        self.page_export_cancel.as_ref().is_some_and(|cancel| Arc::strong_count(cancel) > 1)
+    }
+
+    /// Esc: a futó oldalmentés leállítása; `false`, ha nem fut.
+    pub fn cancel_page_export(&mut self) -> bool {
+        if !self.page_export_running() {
+            return false;
+        }
+        if let Some(cancel) = &self.page_export_cancel {
+            cancel.store(true, Ordering::SeqCst);
+        }
+        true
+    }
+}
+
+fn export_page(source: &Path, format: SaveFormat, page: usize, limits: &image::Limits, target: &Path) -> ViewerResult<()> {
+    let img = decode_page(source, format, page, limits).map_err(|e| ViewerError::load(source, e))?;
+    save_png(&img, target)
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Sender};
+
+use crate::colors::*;
+use crate::file_handlers::detect_format;
+use crate::image_loader::*;
+use crate::view_cache::buffer_bytes;
+
+// A könyvtárlista szomszédos képeinek előzetes betöltése egy háttérszálon,
+// hogy lapozáskor az open_image azonnal kész képet kapjon.
+
+struct PrefetchStore {
+    entries: HashMap<PathBuf, LoadedImage>,
+    used_bytes: usize,
+    limit_bytes: usize,
+}
+
+/// Egy előtöltés; a formátumot már a háttérszál állapítja meg a fájl első bájtjaiból.
+struct PrefetchJob {
+    path: PathBuf,
+    color_settings: ColorSettings,
+    max_texture_size: u32,
+    limits: image::Limits,
+    anim_window_bytes: usize,
+    token: CancelToken,
+}
+
+pub struct PrefetchCache {
+    store: Arc<Mutex<PrefetchStore>>,
+    generation: Arc<AtomicU64>,
+    jobs: Option<Sender<PrefetchJob>>,
+    pub direction: i32, // az utolsó lapozás iránya: 1 előre, -1 vissza
+}
+
+impl Default for PrefetchCache {
+    fn default() -> Self {
+        Self {
+            store: Arc::new(Mutex::new(PrefetchStore {
+                entries: HashMap::new(),
+                used_bytes: 0,
+                limit_bytes: 0,
+            })),
+            generation: Arc::new(AtomicU64::new(0)),
+            jobs: None,
+            direction: 1,
+        }
+    }
+}
+
+/// Egy betöltött kép becsült memóriaigénye bájtban.
+pub fn estimate_bytes(loaded: &LoadedImage) -> usize {
+    let mut bytes = loaded.image.as_bytes().len();
+    if let Some(view) = &loaded.view {
+        bytes += buffer_bytes(&view.rgba_image);
+        if let Some(resized) = &view.resized_image {
+            bytes += resized.as_bytes().len();
+        }
+        if let Some(pyramid) = &view.pyramid {
+            bytes += pyramid.bytes();
+        }
+    }
+    if let Some(anim) = &loaded.anim_data {
+        bytes += anim.bytes(Some(&loaded.image));
+    }
+    bytes
+}
+
+impl PrefetchCache {
+
+    /// Kiveszi a cache-ből a kész képet, ha a háttérszál már betöltötte.
+    pub fn take(&mut self, path: &PathBuf) -> Option<LoadedImage> {
+        let mut store = self.store.lock().ok()?;
+        let loaded = store.entries.remove(path)?;
+        store.used_bytes = store.used_bytes.saturating_sub(estimate_bytes(&loaded));
+        Some(loaded)
+    }
+
+    /// A `wanted` lista (fontossági sorrendben) betöltése; ami már nem kell, kikerül a cache-ből.
+    pub fn schedule(&mut self, wanted: Vec<PathBuf>, color_settings: ColorSettings,
+            max_texture_size: u32, limit_bytes: usize, limits: image::Limits, anim_window_bytes: usize) {
+        let mine = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let Ok(mut store) = self.store.lock() else {
+            return;
+        };
+        store.limit_bytes = limit_bytes;
+        let unwanted: Vec<PathBuf> = store.entries.keys()
+            .filter(|p| !wanted.contains(p))
+            .cloned()
+            .collect();
+        for path in unwanted {
+            if let Some(loaded) = store.entries.remove(&path) {
+                store.used_bytes = store.used_bytes.saturating_sub(estimate_bytes(&loaded));
+            }
+        }
+
+        let store_rc = self.store.clone();
+        let sender = self.jobs.get_or_insert_with(|| spawn_worker(store_rc));
+        for path in wanted {
+            if store.entries.contains_key(&path) {
+                continue;
+            }
+            let token = CancelToken::new(self.generation.clone(), mine);
+            let _ = sender.send(PrefetchJob {
+                path,
+                color_settings,
+                max_texture_size,
+                limits: limits.clone(),
+                anim_window_bytes,
+                token,
+            });
+        }
+    }
+
+    /// Az előtöltött képek becsült memóriája.
+    pub fn bytes(&self) -> usize {
+        self.store.lock().map(|store| store.used_bytes).unwrap_or(0)
+    }
+
+    pub fn clear(&mut self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        if let Ok(mut store) = self.store.lock() {
+            store.entries.clear();
+            store.used_bytes = 0;
+        }
+    }
+}
+
+fn spawn_worker(store: Arc<Mutex<PrefetchStore>>) -> Sender<PrefetchJob> {
+    let (sender, receiver) = channel::<PrefetchJob>();
+    std::thread::spawn(move || {
+        for job in receiver {
+            if job.token.is_cancelled() {
+                continue; // közben újabb lapozás történt
+            }
+            {
+                let Ok(store) = store.lock() else { return; };
+                if store.entries.contains_key(&job.path) || store.used_bytes >= store.limit_bytes {
+                    continue;
+                }
+            }
+            // ismeretlen formátumú fájl kimarad, megnyitáskor a betöltés jelzi a hibát
+            let Ok(format) = detect_format(&job.path) else {
+                continue;
+            };
+            let request = LoadRequest {
+                path: job.path,
+                format,
+                reopen: false,
+                color_settings: job.color_settings,
+                max_texture_size: job.max_texture_size,
+                limits: job.limits,
+                raw_develop: false,
+                page: 0,
+                anim_window_bytes: job.anim_window_bytes,
+            };
+            let Ok(loaded) = decode_guarded(&request, &job.token) else {
+                continue;
+            };
+            let bytes = estimate_bytes(&loaded);
+            let Ok(mut store) = store.lock() else { return; };
+            if store.used_bytes + bytes <= store.limit_bytes {
+                store.used_bytes += bytes;
+                store.entries.insert(request.path, loaded);
+            }
+        }
+    });
+    sender
+}
@@ -0,0 +1,128 @@
+// Fényképezőgép RAW fájlok (DNG, CR2, NEF, ARW).
+// Első lépés: a beágyazott teljes méretű JPEG előnézet azonnal megjelenik.
+// Második, választható lépés: a szenzor adat dekódolása (rawloader), egyszerű bilineáris
+// demozaikolással és a metaadatokban tárolt fehéregyensúllyal. Színmátrix nincs, csak gamma.
+
+use std::io::Read;
+use std::path::Path;
+use image::DynamicImage;
+use rayon::prelude::*;
+
+use crate::exif_my::ExifBlock;
+
+pub const RAW_EXTENSIONS: [&str; 4] = ["dng", "cr2", "nef", "arw"];
+
+/// A RAW fájlból készült kép; preview, ha a beágyazott JPEG-ből származik.
+pub struct RawPicture {
+    pub image: DynamicImage,
+    pub preview: bool,
+}
+
+/// Előnézet (ha van és nem kértek teljes dekódolást), különben demozaikolt kép.
+pub fn decode_raw(path: &Path, develop: bool, limits: &image::Limits) -> Result<RawPicture, String> {
+    if !develop && let Some(image) = embedded_preview(path, limits) {
+        return Ok(RawPicture { image, preview: true });
+    }
+    develop_raw(path, limits).map(|image| RawPicture { image, preview: false })
+}
+
+/// A legnagyobb dekódolható beágyazott JPEG a TIFF könyvtárakból.
+fn embedded_preview(path: &Path, limits: &image::Limits) -> Option<DynamicImage> {
+    let mut data = b"Exif\0\0".to_vec();
+    std::fs::File::open(path).ok()?.read_to_end(&mut data).ok()?;
+    let mut block = ExifBlock::default();
+    block.open_container(data).ok()?;
+    block.embedded_jpegs().into_iter()
+        .filter(|jpeg| !is_lossless_jpeg(jpeg))
+        .find_map(|jpeg| {
+            let mut reader = image::ImageReader::with_format(std::io::Cursor::new(jpeg), image::ImageFormat::Jpeg);
+            reader.limits(limits.clone());
+            reader.decode().ok()
+        })
+}
+
+/// Veszteségmentes (SOF3) JPEG: a CR2/DNG nyers szenzor adata, nem előnézet.
+fn is_lossless_jpeg(data: &[u8]) -> bool {
+    let mut pos = 2;
+    while let (Some(&0xFF), Some(&marker)) = (data.get(pos), data.get(pos + 1)) {
+        if matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            return matches!(marker, 0xC3 | 0xC7 | 0xCB | 0xCF);
+        }
+        let len = data.get(pos + 2..pos + 4).map_or(0, |b| u16::from_be_bytes([b[0], b[1]]) as usize);
+        if len < 2 {
+            return false;
+        }
+        pos += 2 + len;
+    }
+    false
+}
+
+/// A Bayer szenzor adat teljes dekódolása.
+fn develop_raw(path: &Path, limits: &image::Limits) -> Result<DynamicImage, String> {
+    let raw = rawloader::decode_file(path).map_err(|e| e.to_string())?;
+    let rawloader::RawImageData::Integer(data) = &raw.data else {
+        return Err("Floating point RAW data is not supported".into());
+    };
+    if raw.cpp != 1 {
+        return Err("Only Bayer sensor RAW files can be developed".into());
+    }
+    let [top, right, bottom, left] = raw.crops;
+    let width = raw.width.saturating_sub(left + right);
+    let height = raw.height.saturating_sub(top + bottom);
+    if width == 0 || height == 0 {
+        return Err("Empty RAW image".into());
+    }
+    if limits.max_image_width.is_some_and(|max| width as u32 > max)
+        || limits.max_image_height.is_some_and(|max| height as u32 > max)
+        || limits.max_alloc.is_some_and(|max| (width * height * 4) as u64 > max) {
+        return Err(format!("The image is too large ({} x {}), the decode limit is lower", width, height));
+    }
+
+    // fehéregyensúly a zöldhöz normálva, a fekete/fehér szint csatornánként
+    let green = raw.wb_coeffs[1];
+    let wb = raw.wb_coeffs.map(|v| if green.is_finite() && green > 0.0 && v.is_finite() && v > 0.0 { v / green } else { 1.0 });
+    let mut scale = [0f32; 4];
+    for c in 0..4 {
+        let range = raw.whitelevels[c].saturating_sub(raw.blacklevels[c]).max(1) as f32;
+        scale[c] = wb[c] / range;
+    }
+    let gamma: Vec<u8> = (0..4096)
+        .map(|i| ((i as f32 / 4095.0).powf(1.0 / 2.2) * 255.0 + 0.5) as u8)
+        .collect();
+    // (csatorna, normált érték) a vágott kép koordinátáin; a 4. szín (E / második zöld) zöld
+    let sample = |row: usize, col: usize| -> (usize, f32) {
+        let (r, c) = (row + top, col + left);
+        let color = raw.cfa.color_at(r, c).min(3);
+        let value = data.get(r * raw.width + c).copied().unwrap_or(0);
+        let v = value.saturating_sub(raw.blacklevels[color]) as f32 * scale[color];
+        (if color == 3 { 1 } else { color }, v)
+    };
+
+    let mut rgba = vec![0u8; width * height * 4];
+    rgba.par_chunks_mut(width * 4).enumerate().for_each(|(y, line)| {
+        for x in 0..width {
+            let mut sum = [0f32; 3];
+            let mut count = [0u32; 3];
+            for sy in y.saturating_sub(1)..=(y + 1).min(height - 1) {
+                for sx in x.saturating_sub(1)..=(x + 1).min(width - 1) {
+                    let (c, v) = sample(sy, sx);
+                    sum[c] += v;
+                    count[c] += 1;
+                }
+            }
+            // a saját szín a pixel saját értéke, a másik kettő a szomszédok átlaga
+            let (own, own_value) = sample(y, x);
+            sum[own] = own_value;
+            count[own] = 1;
+            let px = &mut line[x * 4..x * 4 + 4];
+            for c in 0..3 {
+                let v = if count[c] > 0 { sum[c] / count[c] as f32 } else { 0.0 };
+                px[c] = gamma[(v.clamp(0.0, 1.0) * 4095.0) as usize];
+            }
+            px[3] = 255;
+        }
+    });
+    image::RgbaImage::from_raw(width as u32, height as u32, rgba)
+        .map(DynamicImage::ImageRgba8)
+        .ok_or_else(|| "RAW buffer size mismatch".to_string())
+}
@@ -0,0 +1,220 @@
+use std::collections::{HashMap, HashSet};
+use slint::{Image, SharedPixelBuffer, Rgba8Pixel, Model, ModelRc, VecModel};
+
+use crate::ImageViewer;
+use crate::TileSlint;
+use crate::colors::*;
+use crate::image_processing::rotate_and_color;
+
+// A textúra méretnél nagyobb képek csempézett megjelenítése.
+// Az eredeti (teljes felbontású) kép megmarad, mellé felezett szintekből álló piramis készül,
+// és csak a ScrollView látható részét fedő csempék készülnek el az aktuális nagyításhoz.
+// Alattuk a kicsinyített kép látszik, amíg a csempék el nem készülnek.
+
+pub const TILE_SIZE: u32 = 512;
+const MAX_CACHED_TILES: usize = 256;
+
+/// Felezett szintek az eredeti kép mellé: levels[0] az 1. szint (fele méret), és így tovább.
+/// A háttérszálon is elkészíthető, ezért nincs benne Slint objektum.
+pub struct TilePyramid {
+    levels: Vec<image::DynamicImage>,
+    full_width: u32,
+    full_height: u32,
+}
+
+impl TilePyramid {
+    pub fn new(image: &image::DynamicImage) -> Self {
+        let mut levels: Vec<image::DynamicImage> = Vec::new();
+        loop {
+            let prev = levels.last().unwrap_or(image);
+            let (w, h) = (prev.width(), prev.height());
+            if w.max(h) <= TILE_SIZE || w < 2 || h < 2 {
+                break;
+            }
+            let next = prev.resize_exact(w.div_ceil(2), h.div_ceil(2), image::imageops::FilterType::Triangle);
+            levels.push(next);
+        }
+        Self { levels, full_width: image.width(), full_height: image.height() }
+    }
+
+    pub fn level_count(&self) -> usize {
+        self.levels.len() + 1
+    }
+
+    /// A piramis által foglalt memória (az eredeti kép nélkül).
+    pub fn bytes(&self) -> usize {
+        self.levels.iter().map(|l| l.as_bytes().len()).sum()
+    }
+
+    /// A teljes felbontású kép mérete forgatás után.
+    pub fn rotated_size(&self, rotate: Rotate) -> (u32, u32) {
+        match rotate {
+            Rotate::Rotate90 | Rotate::Rotate270 => (self.full_height, self.full_width),
+            _ => (self.full_width, self.full_height),
+        }
+    }
+
+    /// A legkisebb szint, amelynek felbontása még eléri a kijelzettet.
+    fn pick_level(&self, magnify: f32) -> usize {
+        for level in (1..self.level_count()).rev() {
+            let scale = self.levels[level - 1].width() as f32 / self.full_width as f32;
+            if scale >= magnify {
+                return level;
+            }
+        }
+        0
+    }
+
+    fn level_image<'a>(&'a self, original: &'a image::DynamicImage, level: usize) -> &'a image::DynamicImage {
+        if level == 0 { original } else { &self.levels[level - 1] }
+    }
+}
+
+/// A forgatott kép egy téglalapjának helye a forgatás előtti képen (w, h: a forgatás előtti kép mérete).
+pub fn source_rect(rotate: Rotate, x: u32, y: u32, tw: u32, th: u32, w: u32, h: u32) -> (u32, u32, u32, u32) {
+    match rotate {
+        Rotate::Rotate90  => (y, h - x - tw, th, tw),
+        Rotate::Rotate180 => (w - x - tw, h - y - th, tw, th),
+        Rotate::Rotate270 => (w - y - th, x, th, tw),
+        _ => (x, y, tw, th),
+    }
+}
+
+/// Egy csempe elkészítése: kivágás, forgatás és színkorrekció.
+/// Élesítésnél a szomszédos pixelek is kellenek, ezért a kivágás a blur sugarával nagyobb.
+pub fn build_tile(src: &image::DynamicImage, colset: &ColorSettings, lut: Option<&Lut4ColorSettings>,
+        x: u32, y: u32, tw: u32, th: u32) -> Image {
+    let (w, h) = (src.width(), src.height());
+    let (rw, rh) = match colset.rotate {
+        Rotate::Rotate90 | Rotate::Rotate270 => (h, w),
+        _ => (w, h),
+    };
+    let margin = if colset.is_blured() && lut.is_some() {
+        (colset.sharpen_radius * 3.0 + 1.0) as u32 + 1
+    } else {
+        0
+    };
+    let px = x.saturating_sub(margin);
+    let py = y.saturating_sub(margin);
+    let pw = (x + tw + margin).min(rw) - px;
+    let ph = (y + th + margin).min(rh) - py;
+    let (sx, sy, sw, sh) = source_rect(colset.rotate, px, py, pw, ph, w, h);
+    let padded = rotate_and_color(&src.crop_imm(sx, sy, sw, sh), colset, lut);
+    let tile = image::imageops::crop_imm(&padded, x - px, y - py, tw, th).to_image();
+    let buffer = SharedPixelBuffer::<Rgba8Pixel>::clone_from_slice(tile.as_raw(), tw, th);
+    Image::from_rgba8(buffer)
+}
+
+/// A UI szálon élő csempézett nézet: a piramis és a már elkészült csempék.
+pub struct TiledView {
+    pub pyramid: TilePyramid,
+    cache: HashMap<(usize, u32, u32), Image>,
+    cache_settings: Option<ColorSettings>,
+}
+
+impl TiledView {
+    pub fn new(pyramid: TilePyramid) -> Self {
+        Self { pyramid, cache: HashMap::new(), cache_settings: None }
+    }
+
+    /// A piramis és a kész csempék memóriája.
+    pub fn bytes(&self) -> usize {
+        self.pyramid.bytes() + self.cache.values()
+            .map(|tile| { let size = tile.size(); (size.width * size.height * 4) as usize })
+            .sum::<usize>()
+    }
+
+    /// A kész csempék eldobása (a láthatók a következő frissítéskor újra elkészülnek).
+    pub fn clear_cache(&mut self) -> bool {
+        let had = !self.cache.is_empty();
+        self.cache.clear();
+        had
+    }
+
+    /// A nézetet (viewport koordinátákban) lefedő csempék; a hiányzókat elkészíti.
+    pub fn visible_tiles(&mut self, original: &image::DynamicImage, colset: &ColorSettings,
+            lut: Option<&Lut4ColorSettings>, magnify: f32,
+            (view_x, view_y, view_w, view_h): (f32, f32, f32, f32)) -> Vec<TileSlint> {
+        if self.cache_settings != Some(*colset) {
+            self.cache.clear();
+            self.cache_settings = Some(*colset);
+        }
+        let level = self.pyramid.pick_level(magnify);
+        let src = self.pyramid.level_image(original, level);
+        let (lw, lh) = match colset.rotate {
+            Rotate::Rotate90 | Rotate::Rotate270 => (src.height(), src.width()),
+            _ => (src.width(), src.height()),
+        };
+        let (full_w, full_h) = self.pyramid.rotated_size(colset.rotate);
+        // egy szint-pixel hány viewport pixel
+        let fx = full_w as f32 / lw as f32 * magnify;
+        let fy = full_h as f32 / lh as f32 * magnify;
+
+        let tiles_x = lw.div_ceil(TILE_SIZE);
+        let tiles_y = lh.div_ceil(TILE_SIZE);
+        let tx0 = ((view_x.max(0.0) / fx) as u32 / TILE_SIZE).min(tiles_x);
+        let ty0 = ((view_y.max(0.0) / fy) as u32 / TILE_SIZE).min(tiles_y);
+        let tx1 = (((view_x + view_w) / fx).ceil() as u32).div_ceil(TILE_SIZE).min(tiles_x);
+        let ty1 = (((view_y + view_h) / fy).ceil() as u32).div_ceil(TILE_SIZE).min(tiles_y);
+
+        let mut visible = HashSet::new();
+        let mut result = Vec::new();
+        for ty in ty0..ty1 {
+            for tx in tx0..tx1 {
+                let x = tx * TILE_SIZE;
+                let y = ty * TILE_SIZE;
+                let tw = TILE_SIZE.min(lw - x);
+                let th = TILE_SIZE.min(lh - y);
+                let key = (level, tx, ty);
+                let image = self.cache.entry(key)
+                    .or_insert_with(|| build_tile(src, colset, lut, x, y, tw, th))
+                    .clone();
+                visible.insert(key);
+                let left = (x as f32 * fx).floor();
+                let top = (y as f32 * fy).floor();
+                result.push(TileSlint {
+                    x: left,
+                    y: top,
+                    width: ((x + tw) as f32 * fx).ceil() - left,
+                    height: ((y + th) as f32 * fy).ceil() - top,
+                    image,
+                });
+            }
+        }
+        if self.cache.len() > MAX_CACHED_TILES {
+            self.cache.retain(|key, _| visible.contains(key));
+        }
+        result
+    }
+}
+
+impl ImageViewer {
+
+    /// A látható csempék frissítése (nagyítás, görgetés, színkorrekció után).
+    pub fn update_tiles(&mut self) {
+        let Some(handle) = &self.ui_handle else { return; };
+        let Some(ui) = handle.upgrade() else { return; };
+        let colset = self.effective_color_settings();
+        if let (Some(tiled), Some(original)) = (&mut self.tiles, &self.original_image) {
+            let needs_lut = colset.is_setted() || colset.is_blured();
+            let local_lut;
+            let lut = match (&self.lut, needs_lut) {
+                (_, false) => None,
+                (Some(lut), true) => Some(lut),
+                (None, true) => { // még nincs LUT, a csempékhez helyben készül
+                    let mut lut = Lut4ColorSettings::default();
+                    lut.update_lut(&colset);
+                    local_lut = lut;
+                    Some(&local_lut)
+                }
+            };
+            let state = ui.get_img_state();
+            let tiles = tiled.visible_tiles(original, &colset, lut, self.magnify,
+                (-ui.get_offset_x(), -ui.get_offset_y(), state.window_width, state.window_height));
+            ui.set_tiles(ModelRc::new(VecModel::from(tiles)));
+        }
+        else if ui.get_tiles().row_count() > 0 {
+            ui.set_tiles(ModelRc::default());
+        }
+    }
+}
@@ -0,0 +1,346 @@
+// Animáció idősáv: képkocka bélyegképek a késleltetéssel, csúszka, lejátszási sebesség,
+// oda-vissza (ping-pong) lejátszás és a képkockák PNG exportja.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use slint::{ComponentHandle, Image, Model, ModelRc, Rgba8Pixel, SharedPixelBuffer, VecModel};
+
+use crate::ImageViewer;
+use crate::{TimelineFrame, TimelineWindow};
+use crate::anim_stream::FrameSource;
+use crate::atomic_save::save_png;
+use crate::errors::*;
+use crate::image_loader::CancelToken;
+use crate::image_processing::worker_status;
+
+/// A háttérszál üzenete: egy kész bélyegkép (a fájlbeli sorszámmal) vagy a dekódolás hibája.
+type ThumbMessage = (u64, Result<(usize, SharedPixelBuffer<Rgba8Pixel>), String>);
+
+/// Az idősáv bélyegképei a fájl képkockáihoz. Háttérszálon készülnek, és érkezéskor
+/// kerülnek a helyükre; új animációnál a futó szál a generáció váltásával leáll.
+pub struct TimelineThumbs {
+    pub images: Vec<Image>,
+    started: bool,
+    generation: Arc<AtomicU64>,
+    sender: Sender<ThumbMessage>,
+    receiver: Receiver<ThumbMessage>,
+}
+
+impl Default for TimelineThumbs {
+    fn default() -> Self {
+        let (sender, receiver) = channel();
+        Self {
+            images: Vec::new(),
+            started: false,
+            generation: Arc::new(AtomicU64::new(0)),
+            sender,
+            receiver,
+        }
+    }
+}
+
+impl TimelineThumbs {
+
+    /// Új animációnál: a képek törlése, a még futó szál leállítása.
+    pub fn clear(&mut self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.images.clear();
+        self.started = false;
+    }
+
+    /// A háttérszál indítása, ha ehhez az animációhoz még nem indult.
+    fn start(&mut self, source: FrameSource, t_handle: slint::Weak<TimelineWindow>) {
+        if self.started {
+            return;
+        }
+        self.started = true;
+        let mine = self.generation.load(Ordering::SeqCst);
+        let token = CancelToken::new(self.generation.clone(), mine);
+        let sender = self.sender.clone();
+        std::thread::spawn(move || {
+            let notify = || {
+                let t_handle = t_handle.clone();
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(t_ui) = t_handle.upgrade() {
+                        t_ui.invoke_thumbs_ready();
+                    }
+                });
+            };
+            // a bélyegképekhez minden képkockát végigdekódol, de csak a kicsinyített kép marad meg
+            let result = source.for_each_frame(|i, frame| {
+                if token.is_cancelled() {
+                    return Err("cancelled".into());
+                }
+                let thumb = frame.thumbnail(64, 64).to_rgba8();
+                let buffer = SharedPixelBuffer::<Rgba8Pixel>::clone_from_slice(thumb.as_raw(), thumb.width(), thumb.height());
+                let _ = sender.send((mine, Ok((i, buffer))));
+                notify();
+                Ok(())
+            });
+            if let Err(e) = result && !token.is_cancelled() {
+                let _ = sender.send((mine, Err(e)));
+                notify();
+            }
+        });
+    }
+
+    /// Az azóta elkészült bélyegképek a helyükre; a frissült fájlbeli sorszámok és az esetleges hiba.
+    fn receive(&mut self) -> (Vec<usize>, Option<String>) {
+        let current = self.generation.load(Ordering::SeqCst);
+        let mut arrived = Vec::new();
+        let mut error = None;
+        while let Ok((generation, message)) = self.receiver.try_recv() {
+            if generation != current {
+                continue;
+            }
+            match message {
+                Ok((i, buffer)) => {
+                    if self.images.len() <= i {
+                        self.images.resize(i + 1, Image::default());
+                    }
+                    self.images[i] = Image::from_rgba8(buffer);
+                    arrived.push(i);
+                }
+                Err(e) => error = Some(e),
+            }
+        }
+        (arrived, error)
+    }
+}
+
+impl ImageViewer {
+
+    /// Az idősáv ablak megjelenítése / elrejtése.
+    pub fn toggle_timeline(&mut self) {
+        if self.anim_data.is_none() && !self.show_timeline {
+            return;
+        }
+        self.show_timeline = !self.show_timeline;
+        if self.show_timeline {
+            self.refresh_timeline();
+        }
+        if let Some(t_ui) = &self.timeline_window {
+            if self.show_timeline {
+                t_ui.show().unwrap();
+            } else {
+                t_ui.hide().unwrap();
+            }
+        }
+    }
+
+    /// A bélyegképek újraépítése (új animáció betöltésekor, szerkesztés után).
+    pub fn refresh_timeline(&mut self) {
+        let Some(t_ui) = &self.timeline_window else {
+            return;
+        };
+        // a még el nem készült bélyegképek helye üres, a háttérszál tölti fel
+        let mut frames: Vec<TimelineFrame> = Vec::new();
+        if let Some(anim) = &self.anim_data {
+            self.timeline_thumbs.start(anim.frames.source(), t_ui.as_weak());
+            // a szerkesztett sorrendben, a saját késleltetéssel
+            frames = anim.order.iter().zip(&anim.delays).map(|(src, delay)| TimelineFrame {
+                thumb: self.timeline_thumbs.images.get(*src).cloned().unwrap_or_default(),
+                delay: format!("{} ms", delay.as_millis()).into(),
+            }).collect();
+        }
+        t_ui.set_total(frames.len() as i32);
+        t_ui.set_frames(ModelRc::new(VecModel::from(frames)));
+        t_ui.set_speed(self.anim_speed);
+        t_ui.set_ping_pong(self.anim_ping_pong);
+        self.sync_onion_controls();
+        self.sync_timeline();
+    }
+
+    /// A háttérszálon elkészült bélyegképek beírása az idősáv soraiba.
+    pub fn receive_timeline_thumbs(&mut self) {
+        let (arrived, error) = self.timeline_thumbs.receive();
+        if let Some(e) = error && let Some(path) = &self.shown_image {
+            self.notify_error(&ViewerError::load(path, format!("timeline: {}", e)));
+        }
+        let (Some(t_ui), Some(anim)) = (&self.timeline_window, &self.anim_data) else {
+            return;
+        };
+        if arrived.is_empty() {
+            return;
+        }
+        let model = t_ui.get_frames();
+        for (row, src) in anim.order.iter().enumerate() {
+            if !arrived.contains(src) {
+                continue;
+            }
+            if let (Some(mut data), Some(thumb)) = (model.row_data(row), self.timeline_thumbs.images.get(*src)) {
+                data.thumb = thumb.clone();
+                model.set_row_data(row, data);
+            }
+        }
+    }
+
+    /// Az aktuális képkocka és a lejátszás állapota az idősávon.
+    pub fn sync_timeline(&self) {
+        if !self.show_timeline {
+            return;
+        }
+        if let Some(t_ui) = &self.timeline_window {
+            t_ui.set_current(self.current_frame as i32);
+            t_ui.set_scrub(self.current_frame as f32);
+            t_ui.set_playing(self.anim_playing);
+        }
+    }
+
+    /// Ugrás a kért képkockára; a lejátszás megáll.
+    pub fn seek_frame(&mut self, frame: i32) {
+        let Some(anim) = &self.anim_data else {
+            return;
+        };
+        let frame = (frame.max(0) as usize).min(anim.total_frames.saturating_sub(1));
+        if frame == self.current_frame && !self.anim_playing {
+            return;
+        }
+        self.anim_playing = false;
+        self.anim_timer.stop();
+        self.show_frame(frame);
+    }
+
+    /// A lejátszás következő képkockája (az időzítő hívja).
+    /// Ping-pong módban a végeken irányt vált; a lejátszások elfogyásakor az utolsó (ping-pong: az első) kockán megáll.
+    pub fn advance_animation(&mut self) {
+        let Some(total) = self.anim_data.as_ref().map(|a| a.total_frames) else {
+            return;
+        };
+        if !self.anim_playing || total < 2 {
+            return;
+        }
+        let cur = self.current_frame.min(total - 1);
+        let next = if self.anim_ping_pong {
+            if self.anim_reverse {
+                if cur == 0 {
+                    self.anim_reverse = false;
+                    if self.finish_play() {
+                        return;
+                    }
+                    1
+                } else {
+                    cur - 1
+                }
+            } else if cur + 1 >= total {
+                self.anim_reverse = true;
+                cur - 1
+            } else {
+                cur + 1
+            }
+        } else if cur + 1 >= total {
+            if self.finish_play() {
+                return;
+            }
+            0
+        } else {
+            cur + 1
+        };
+        self.show_frame(next);
+    }
+
+    /// Egy lejátszás vége; true, ha nincs több ismétlés és a lejátszás megállt.
+    fn finish_play(&mut self) -> bool {
+        self.anim_plays_done += 1;
+        if !self.plays_exhausted() {
+            return false;
+        }
+        self.anim_playing = false;
+        self.sync_timeline();
+        self.update_onion_skin();
+        true
+    }
+
+    /// Bekapcsolt Loop mellett soha; különben a fájl ismétlésszáma (végtelennél egy) után.
+    pub fn plays_exhausted(&self) -> bool {
+        let plays = self.anim_data.as_ref().map_or(1, |anim| anim.plays.max(1));
+        !self.anim_loop && self.anim_plays_done >= plays
+    }
+
+    /// Az aktuális képkocka mentése PNG-be (korrekciók nélkül).
+    pub fn export_current_frame(&mut self) {
+        let Some(frame) = self.anim_data.as_ref().and_then(|a| a.frame(self.current_frame)) else {
+            return;
+        };
+        let mut dialog = rfd::FileDialog::new()
+            .add_filter("PNG", &["png"])
+            .set_file_name(format!("{}_f{:03}.png", self.frame_stem(), self.current_frame + 1));
+        if let Some(parent) = self.shown_image.as_ref().and_then(|p| p.parent()) {
+            dialog = dialog.set_directory(parent);
+        }
+        let Some(target) = dialog.save_file() else {
+            return;
+        };
+        match save_png(&frame, &target) {
+            Ok(()) => self.notify(&format!("Frame {} exported to {}", self.current_frame + 1, target.display())),
+            Err(e) => self.notify_error(&e),
+        }
+    }
+
+    /// Minden képkocka mentése a kiválasztott könyvtárba (név_f001.png, ...), háttérszálon;
+    /// a haladás az értesítési sávban látszik, Esc leállítja.
+    pub fn export_all_frames(&mut self) {
+        let Some(anim) = self.anim_data.clone() else {
+            return;
+        };
+        let Some(weak) = self.ui_handle.clone() else {
+            return;
+        };
+        if self.frame_export_running() {
+            self.notify("Frame export is already running");
+            return;
+        }
+        let mut dialog = rfd::FileDialog::new();
+        if let Some(parent) = self.shown_image.as_ref().and_then(|p| p.parent()) {
+            dialog = dialog.set_directory(parent);
+        }
+        let Some(dir) = dialog.pick_folder() else {
+            return;
+        };
+        let stem = self.frame_stem();
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.frame_export_cancel = Some(cancel.clone());
+        std::thread::spawn(move || {
+            let status = |text: String, done: bool| worker_status(&weak, text, done);
+            let count = anim.order.len();
+            let result = anim.for_each_frame(|i, frame| {
+                if cancel.load(Ordering::SeqCst) {
+                    return Err(format!("cancelled after {} frames", i));
+                }
+                status(format!("Exporting frame {} / {} ... (Esc: cancel)", i + 1, count), false);
+                let target: PathBuf = dir.join(format!("{}_f{:03}.png", stem, i + 1));
+                save_png(frame, &target).map_err(|e| e.to_string())
+            });
+            match result {
+                Ok(()) => status(format!("{} frames exported to {}", count, dir.display()), true),
+                Err(e) => status(ViewerError::save(&dir, e).to_string(), true),
+            }
+        });
+    }
+
+    /// Fut-e még a képkockák mentése (a szál a saját példányát a végén elengedi).
+    fn frame_export_running(&self) -> bool {
+        self.frame_export_cancel.as_ref().is_some_and(|cancel| Arc::strong_count(cancel) > 1)
+    }
+
+    /// Esc: a futó képkocka mentés leállítása; `false`, ha nem fut.
+    pub fn cancel_frame_export(&mut self) -> bool {
+        if !self.frame_export_running() {
+            return false;
+        }
+        if let Some(cancel) = &self.frame_export_cancel {
+            cancel.store(true, Ordering::SeqCst);
+        }
+        true
+    }
+
+    fn frame_stem(&self) -> String {
+        self.shown_image.as_ref()
+            .and_then(|p| p.file_stem())
+            .and_then(|s| s.to_str())
+            .unwrap_or("frame")
+            .to_string()
+    }
+}
@@ -0,0 +1,124 @@
+use slint::{Image, SharedPixelBuffer, Rgba8Pixel};
+
+use crate::colors::*;
+
+// A review() lépéseinek gyorsítótára. A lépések és amitől függnek:
+//   base:      forgatott RGBA (rotate + to_rgba8)  <- kép generáció, kicsinyítés, forgatás
+//   corrected: LUT + élesítés                      <- base, színbeállítások, GPU
+//   image:     slint::Image                        <- corrected
+// Nagyításkor, görgetéskor semmi sem készül újra; csatorna kapcsolásnál csak a corrected és az image.
+// A pufferek referenciaszámláltak: a Slint kép ugyanazt a memóriát látja, a corrected pedig
+// csak akkor lesz külön másolat, amikor a színkorrekció ténylegesen írni kezd bele (copy-on-write).
+
+pub type RgbaBuffer = SharedPixelBuffer<Rgba8Pixel>;
+
+/// RgbaImage átalakítása Slint pufferré (egy másolás; a forrás ezután eldobható).
+pub fn rgba_to_buffer(rgba: &image::RgbaImage) -> RgbaBuffer {
+    RgbaBuffer::clone_from_slice(rgba.as_raw(), rgba.width(), rgba.height())
+}
+
+pub fn buffer_bytes(buffer: &RgbaBuffer) -> usize {
+    buffer.as_bytes().len()
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BaseKey {
+    pub generation: u64,
+    pub resized: bool,
+    pub rotate: Rotate,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CorrectedKey {
+    pub base: BaseKey,
+    pub colset: ColorSettings,
+    pub gpu: bool,
+}
+
+#[derive(Default)]
+pub struct ViewCache {
+    base_key: Option<BaseKey>,
+    base: Option<RgbaBuffer>,
+    corrected_key: Option<CorrectedKey>,
+    corrected: Option<RgbaBuffer>, // None, ha nincs színkorrekció: a base látszik
+    image: Option<Image>,
+}
+
+impl ViewCache {
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn has_base(&self, key: &BaseKey) -> bool {
+        self.base_key.as_ref() == Some(key) && self.base.is_some()
+    }
+
+    pub fn has_corrected(&self, key: &CorrectedKey) -> bool {
+        self.corrected_key.as_ref() == Some(key) && (self.corrected.is_some() || self.has_base(&key.base))
+    }
+
+    pub fn set_base(&mut self, key: BaseKey, base: RgbaBuffer) {
+        self.base_key = Some(key);
+        self.base = Some(base);
+        self.corrected_key = None;
+        self.corrected = None;
+        self.image = None;
+    }
+
+    pub fn base(&self) -> Option<&RgbaBuffer> {
+        self.base.as_ref()
+    }
+
+    /// `corrected == None`: a base változatlanul látszik.
+    pub fn set_corrected(&mut self, key: CorrectedKey, corrected: Option<RgbaBuffer>) {
+        self.corrected_key = Some(key);
+        self.corrected = corrected;
+        self.image = None;
+    }
+
+    /// A háttérszálon előkészített, már színkorrigált kép átvétele (a base nélkül).
+    pub fn set_prepared(&mut self, key: CorrectedKey, rgba: RgbaBuffer, corrected: bool) {
+        if corrected {
+            self.base_key = None;
+            self.base = None;
+            self.corrected_key = Some(key);
+            self.corrected = Some(rgba);
+            self.image = None;
+        } else {
+            self.set_base(key.base, rgba);
+            self.corrected_key = Some(key);
+        }
+    }
+
+    /// A megjelenített (színkorrigált) RGBA kép.
+    pub fn rgba(&self) -> Option<&RgbaBuffer> {
+        self.corrected.as_ref().or(self.base.as_ref())
+    }
+
+    /// A Slint kép; a megjelenített pufferre mutat, nem másol.
+    pub fn image(&mut self) -> Option<Image> {
+        if self.image.is_none() {
+            self.image = Some(Image::from_rgba8(self.rgba()?.clone()));
+        }
+        self.image.clone()
+    }
+
+    /// Memóriakeret túllépésekor: ha van külön színkorrigált kép, a base eldobható
+    /// (a következő színváltoztatáskor újra elkészül).
+    pub fn drop_base(&mut self) -> bool {
+        if self.corrected.is_some() && self.base.is_some() {
+            self.base = None;
+            self.base_key = None;
+            return true;
+        }
+        false
+    }
+
+    /// A foglalt memória bájtban; a megjelenített kép a pufferrel közös, nem számít külön.
+    pub fn bytes(&self) -> usize {
+        self.base.as_ref().map(buffer_bytes).unwrap_or(0)
+            + self.corrected.as_ref().map(buffer_bytes).unwrap_or(0)
+    }
+
+}